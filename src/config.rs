@@ -4,11 +4,15 @@ use serde::Deserialize;
 use std::default::Default;
 use std::{str, env};
 use crate::database::models::punishment::PunishmentType;
+use crate::database::models::session::ConcurrentSessionPolicy;
 use crate::util::webhook::WebhookUtils;
+use crate::socket::leaderboard::LeaderboardPeriodSettings;
 
 use super::database::models::level_color::LevelColor;
 use super::database::models::join_sound::JoinSound;
 use super::database::models::broadcast::Broadcast;
+use super::database::models::rank_promotion::RankPromotion;
+use super::database::models::level_reward::LevelReward;
 use super::util::file::{read_file, deserialize_properties_file};
 
 #[derive(Debug)]
@@ -98,6 +102,13 @@ async fn deserialize_mars_options() -> Result<MarsConfigOptions, ConfigDeseriali
             "webhooks.reports" => { config.reports_webhook_url = v.to_string(); },
             "webhooks.notes" => { config.notes_webhook_url = v.to_string(); },
             "webhooks.debug" => { config.debug_log_webhook_url = v.to_string(); },
+            "leaderboards.timezone" => { if let Ok(tz) = v.to_string().parse::<chrono_tz::Tz>() { config.leaderboard_periods.timezone = tz; } },
+            "leaderboards.week-start" => { if let Ok(day) = v.to_string().parse::<chrono::Weekday>() { config.leaderboard_periods.week_start = day; } },
+            "leaderboards.legacy-period-ids" => { if let Ok(b) = v.to_string().parse::<bool>() { config.leaderboard_periods.legacy_ids = b; } },
+            "leaderboards.ratio-min-matches" => { if let Ok(i) = v.to_string().parse::<u64>() { config.ratio_min_matches = i; } },
+            "leaderboards.ratio-min-bow-shots" => { if let Ok(i) = v.to_string().parse::<u64>() { config.ratio_min_bow_shots = i; } },
+            "leaderboards.ratio-min-playtime-hours" => { if let Ok(i) = v.to_string().parse::<u64>() { config.ratio_min_playtime_hours = i; } },
+            "sessions.concurrent-policy" => { if let Ok(policy) = v.to_string().parse::<ConcurrentSessionPolicy>() { config.concurrent_session_policy = policy; } },
             _ => {}
         }
     });
@@ -109,17 +120,23 @@ async fn deserialize_mars_data() -> Result<MarsConfigData, ConfigDeserializeErro
     let join_sounds_path = env::var("MARS_JOIN_SOUNDS_PATH").unwrap_or("./join_sounds.yml".to_string());
     let broadcasts_path = env::var("MARS_BROADCASTS_PATH").unwrap_or("./broadcasts.yml".to_string());
     let pun_types_path = env::var("MARS_PUNTYPES_PATH").unwrap_or("./punishment_types.yml".to_string());
+    let rank_promotions_path = env::var("MARS_RANK_PROMOTIONS_PATH").unwrap_or("./rank_promotions.yml".to_string());
+    let level_rewards_path = env::var("MARS_LEVEL_REWARDS_PATH").unwrap_or("./level_rewards.yml".to_string());
 
     let (
         level_colors, 
         join_sounds, 
         broadcasts, 
-        punishment_types
+        punishment_types,
+        rank_promotions,
+        level_rewards
     ) = match tokio::try_join!(
         deserialize_mars_data_component::<Vec<LevelColor>>(&level_colors_path),
         deserialize_mars_data_component::<Vec<JoinSound>>(&join_sounds_path),
         deserialize_mars_data_component::<Vec<Broadcast>>(&broadcasts_path),
-        deserialize_mars_data_component::<Vec<PunishmentType>>(&pun_types_path)
+        deserialize_mars_data_component::<Vec<PunishmentType>>(&pun_types_path),
+        deserialize_optional_mars_data_component::<Vec<RankPromotion>>(&rank_promotions_path),
+        deserialize_optional_mars_data_component::<Vec<LevelReward>>(&level_rewards_path)
     ) {
         Ok(values) => values,
         Err(e) => return Err(e)
@@ -128,7 +145,9 @@ async fn deserialize_mars_data() -> Result<MarsConfigData, ConfigDeserializeErro
         level_colors,
         join_sounds,
         broadcasts,
-        punishment_types
+        punishment_types,
+        rank_promotions,
+        level_rewards
     })
 }
 
@@ -146,6 +165,16 @@ async fn deserialize_mars_data_component<T: DeserializeOwned>(
     Ok(data)
 }
 
+// optional components fall back to their default when the file does not exist
+async fn deserialize_optional_mars_data_component<T: DeserializeOwned + Default>(
+    file_path: &String,
+) -> Result<T, ConfigDeserializeError> {
+    match deserialize_mars_data_component::<T>(file_path).await {
+        Err(ConfigDeserializeError::IOError(io_err)) if io_err.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        result => result
+    }
+}
+
 pub struct MarsConfig {
     pub token: String,
     pub options: MarsConfigOptions,
@@ -173,7 +202,12 @@ pub struct MarsConfigOptions {
     pub punishments_webhook_url: String,
     pub reports_webhook_url: String,
     pub notes_webhook_url: String,
-    pub debug_log_webhook_url: String
+    pub debug_log_webhook_url: String,
+    pub leaderboard_periods: LeaderboardPeriodSettings,
+    pub ratio_min_matches: u64,
+    pub ratio_min_bow_shots: u64,
+    pub ratio_min_playtime_hours: u64,
+    pub concurrent_session_policy: ConcurrentSessionPolicy
 }
 
 impl Default for MarsConfigOptions {
@@ -188,6 +222,11 @@ impl Default for MarsConfigOptions {
             reports_webhook_url: String::new(),
             notes_webhook_url: String::new(),
             debug_log_webhook_url: String::new(),
+            leaderboard_periods: LeaderboardPeriodSettings::default(),
+            ratio_min_matches: 50,
+            ratio_min_bow_shots: 200,
+            ratio_min_playtime_hours: 10,
+            concurrent_session_policy: ConcurrentSessionPolicy::default()
         }
     }
 }
@@ -197,5 +236,7 @@ pub struct MarsConfigData {
     pub level_colors: Vec<LevelColor>,
     pub join_sounds: Vec<JoinSound>,
     pub broadcasts: Vec<Broadcast>,
-    pub punishment_types: Vec<PunishmentType>
+    pub punishment_types: Vec<PunishmentType>,
+    pub rank_promotions: Vec<RankPromotion>,
+    pub level_rewards: Vec<LevelReward>
 }
@@ -1,7 +1,7 @@
-use std::{str::FromStr, time::Duration};
+use std::{str::FromStr, time::Duration, collections::HashMap};
 
 use mars_api_rs_macro::IdentifiableDocument;
-use mongodb::{options::{ClientOptions, FindOneOptions, UpdateOptions}, Client, Collection, bson::{doc, oid::ObjectId}, Cursor, results::DeleteResult};
+use mongodb::{options::{ClientOptions, FindOneOptions, FindOptions, UpdateOptions}, Client, Collection, bson::{doc, oid::ObjectId, Document}, Cursor, results::DeleteResult};
 use models::tag::Tag;
 use rand::Rng;
 use rocket::serde::DeserializeOwned;
@@ -9,9 +9,9 @@ use futures::StreamExt;
 use serde::Serialize;
 use anyhow::anyhow;
 
-use crate::{database::models::player::Player, util::r#macro::unwrap_helper};
+use crate::{database::models::player::{Player, PlayerSearchDocument}, util::{r#macro::unwrap_helper, time::get_u64_time_millis}};
 
-use self::models::{session::Session, punishment::Punishment, rank::Rank, r#match::Match, level::Level, death::Death};
+use self::models::{session::Session, punishment::Punishment, rank::Rank, r#match::Match, level::Level, death::Death, server::XPMultiplierWindow, leaderboard_snapshot::LeaderboardSnapshot, rating::RatingChange, level_stats::LevelStats, level_record::LevelRecordEntry, rotation::Rotation, level_rating::{LevelRating, LevelRatingAggregate}};
 
 pub mod models;
 pub mod cache;
@@ -30,7 +30,14 @@ pub struct Database {
     pub ranks: Collection<Rank>,
     pub matches: Collection<Match>,
     pub deaths: Collection<Death>,
-    pub levels: Collection<Level>
+    pub levels: Collection<Level>,
+    pub xp_multiplier_windows: Collection<XPMultiplierWindow>,
+    pub leaderboard_snapshots: Collection<LeaderboardSnapshot>,
+    pub rating_changes: Collection<RatingChange>,
+    pub level_stats: Collection<LevelStats>,
+    pub level_records: Collection<LevelRecordEntry>,
+    pub rotations: Collection<Rotation>,
+    pub level_ratings: Collection<LevelRating>
 }
 
 impl Database {
@@ -105,6 +112,34 @@ impl Database {
         }
     }
 
+    pub async fn get_active_xp_multiplier_windows(&self, server_id: &str) -> Vec<XPMultiplierWindow> {
+        let time_millis = get_u64_time_millis() as i64;
+        Database::consume_cursor_into_owning_vec_option(self.xp_multiplier_windows.find(doc! {
+            "startsAt": {"$lte": time_millis},
+            "endsAt": {"$gt": time_millis},
+            // window server ids are stored lowercased
+            "$or": [{"serverId": null}, {"serverId": server_id.to_lowercase()}]
+        }, None).await.ok()).await
+    }
+
+    // averages the matching ratings grouped by a rating field, e.g. levelId or levelVersion
+    pub async fn get_level_rating_aggregates(&self, filter: Document, group_field: &str) -> HashMap<String, LevelRatingAggregate> {
+        let pipeline = vec![
+            doc! { "$match": filter },
+            doc! { "$group": { "_id": format!("${}", group_field), "average": { "$avg": "$score" }, "count": { "$sum": 1 } } }
+        ];
+        let mut cursor = unwrap_helper::result_return_default!(self.level_ratings.aggregate(pipeline, None).await, HashMap::new());
+        let mut aggregates : HashMap<String, LevelRatingAggregate> = HashMap::new();
+        while let Some(Ok(group)) = cursor.next().await {
+            let key = unwrap_helper::continue_default!(group.get_str("_id").ok());
+            aggregates.insert(key.to_owned(), LevelRatingAggregate {
+                average: group.get_f64("average").unwrap_or(0.0),
+                count: group.get_i32("count").unwrap_or(0) as u32
+            });
+        };
+        aggregates
+    }
+
     pub async fn get_player_punishments(&self, player: &Player) -> Vec<Punishment> {
         if let Ok(punishments_cursor) = self.punishments.find(doc! { "target.id": player.id.to_owned() }, None).await {
             let mut puns : Vec<Punishment> = vec![];
@@ -134,6 +169,33 @@ impl Database {
         }
     }
 
+    // case-insensitive over current and past names, most recently joined first
+    pub async fn search_player_names(&self, pattern: &str, limit: i64) -> Vec<PlayerSearchDocument> {
+        let opts = FindOptions::builder()
+            .projection(doc! { "_id": 1, "name": 1, "nameLower": 1, "lastJoinedAt": 1, "nameHistory": 1 })
+            .sort(doc! { "lastJoinedAt": -1 })
+            .limit(limit)
+            .build();
+        let cursor = unwrap_helper::result_return_default!(self.players.clone_with_type::<PlayerSearchDocument>().find(doc! {
+            "$or": [{ "nameLower": { "$regex": pattern } }, { "nameHistory.nameLower": { "$regex": pattern } }]
+        }, opts).await, Vec::new());
+        Database::consume_cursor_into_owning_vec(cursor).await
+    }
+
+    // whoever used the name most recently, current names aren't considered
+    pub async fn find_player_by_past_name(&self, name: &str) -> Option<Player> {
+        let name_lower = name.to_lowercase();
+        let cursor = unwrap_helper::result_return_default!(self.players.find(doc! {
+            "nameHistory.nameLower": &name_lower
+        }, None).await, None);
+        Database::consume_cursor_into_owning_vec(cursor).await.into_iter().max_by_key(|player| {
+            player.name_history.iter()
+                .filter(|entry| entry.name_lower == name_lower)
+                .map(|entry| entry.last_seen_at)
+                .max()
+        })
+    }
+
     pub async fn get_alts_for_player(&self, player: &Player) -> Vec<Player> {
         let cursor = unwrap_helper::result_return_default!(self.players.find(doc! {
             "ips": {"$in": &player.ips}, "_id": {"$ne": &player.id}
@@ -197,7 +259,14 @@ pub async fn connect(db_url: &String, min_pool_size: Option<u32>, max_pool_size:
     let matches = db.collection::<Match>(Match::get_collection_name());
     let levels = db.collection::<Level>(Level::get_collection_name());
     let deaths = db.collection::<Death>(Death::get_collection_name());
+    let xp_multiplier_windows = db.collection::<XPMultiplierWindow>(XPMultiplierWindow::get_collection_name());
+    let leaderboard_snapshots = db.collection::<LeaderboardSnapshot>(LeaderboardSnapshot::get_collection_name());
+    let rating_changes = db.collection::<RatingChange>(RatingChange::get_collection_name());
+    let level_stats = db.collection::<LevelStats>(LevelStats::get_collection_name());
+    let level_records = db.collection::<LevelRecordEntry>(LevelRecordEntry::get_collection_name());
+    let rotations = db.collection::<Rotation>(Rotation::get_collection_name());
+    let level_ratings = db.collection::<LevelRating>(LevelRating::get_collection_name());
 
     info!("Connected to database successfully.");
-    Ok(Database { mongo: db, tags, players, sessions, punishments, ranks, matches, levels, deaths })
+    Ok(Database { mongo: db, tags, players, sessions, punishments, ranks, matches, levels, deaths, xp_multiplier_windows, leaderboard_snapshots, rating_changes, level_stats, level_records, rotations, level_ratings })
 }
@@ -0,0 +1,43 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Serialize, Deserialize};
+
+use crate::{database::CollectionOwner, socket::leaderboard::{ScoreType, LeaderboardPeriod}};
+
+#[derive(Serialize, Deserialize, IdentifiableDocument)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardSnapshot {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub score_type: ScoreType,
+    pub period: LeaderboardPeriod,
+    pub period_id: String,
+    pub entries: Vec<LeaderboardSnapshotEntry>,
+    pub created_at: u64
+}
+
+impl LeaderboardSnapshot {
+    pub fn get_snapshot_id(score_type: &ScoreType, period_id: &str) -> String {
+        format!("{}:{}", score_type, period_id)
+    }
+}
+
+impl CollectionOwner<LeaderboardSnapshot> for LeaderboardSnapshot {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<LeaderboardSnapshot> {
+        &database.leaderboard_snapshots
+    }
+
+    fn get_collection_name() -> &'static str {
+        "leaderboard_snapshot"
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardSnapshotEntry {
+    pub position: u32,
+    pub id: String,
+    pub name: String,
+    pub score: u32
+}
@@ -1,10 +1,12 @@
+use std::collections::HashMap;
+
 use mars_api_rs_macro::IdentifiableDocument;
 use mars_api_rs_derive::IdentifiableDocument;
 use serde::{Serialize, Deserialize};
 
 use crate::database::CollectionOwner;
 
-use super::{r#match::GoalCollection, player::{PlayerRecord, ProjectileRecord, FirstBloodRecord}};
+use super::{r#match::GoalCollection, player::{PlayerRecord, ProjectileRecord, FirstBloodRecord, SimplePlayer}};
 
 #[derive(Serialize, Deserialize, IdentifiableDocument)]
 #[serde(rename_all = "camelCase")]
@@ -24,9 +26,23 @@ pub struct Level {
     pub goals: Option<GoalCollection>,
     #[serde(default)]
     pub last_match_id: Option<String>,
+    // left out of projected map listings
+    #[serde(default)]
     pub records: LevelRecords
 }
 
+impl Level {
+    pub fn get_contributor_ids(&self) -> Vec<String> {
+        self.authors.iter().chain(self.contributors.iter()).map(|contributor| contributor.uuid.clone()).collect()
+    }
+
+    pub fn resolve_contributors(&mut self, players: &HashMap<String, SimplePlayer>) {
+        for contributor in self.authors.iter_mut().chain(self.contributors.iter_mut()) {
+            contributor.player = players.get(&contributor.uuid).cloned();
+        };
+    }
+}
+
 impl CollectionOwner<Level> for Level {
     fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<Level> {
         &database.levels
@@ -73,11 +89,14 @@ impl Default for LevelRecords {
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LevelContributor {
-    uuid: String,
-    contribution: Option<String>
+    pub uuid: String,
+    pub contribution: Option<String>,
+    // filled in when maps are served, never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player: Option<SimplePlayer>
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, strum_macros::EnumProperty, Hash, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, strum_macros::EnumProperty, strum_macros::EnumString, strum_macros::Display, Hash, PartialEq, Eq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum LevelGamemode {
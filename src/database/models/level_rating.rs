@@ -0,0 +1,52 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Serialize, Deserialize};
+
+use crate::database::CollectionOwner;
+
+use super::player::SimplePlayer;
+
+pub const MIN_RATING_SCORE : u8 = 1;
+pub const MAX_RATING_SCORE : u8 = 5;
+pub const MAX_RATING_COMMENT_LENGTH : usize = 500;
+
+// one per player per map version, rating again replaces the previous one
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelRating {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub level_id: String,
+    pub level_version: String,
+    pub player: SimplePlayer,
+    pub match_id: String,
+    pub score: u8,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64
+}
+
+impl LevelRating {
+    pub fn get_id(level_id: &str, level_version: &str, player_id: &str) -> String {
+        format!("{}:{}:{}", level_id, level_version, player_id)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelRatingAggregate {
+    pub average: f64,
+    pub count: u32
+}
+
+impl CollectionOwner<LevelRating> for LevelRating {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<LevelRating> {
+        &database.level_ratings
+    }
+
+    fn get_collection_name() -> &'static str {
+        "level_rating"
+    }
+}
@@ -0,0 +1,61 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Serialize, Deserialize};
+
+use crate::database::CollectionOwner;
+
+use super::{player::SimplePlayer, level::LevelRecords};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, strum_macros::EnumIter, strum_macros::EnumString, strum_macros::Display)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE", ascii_case_insensitive)]
+pub enum LevelRecordType {
+    HighestKillstreak,
+    LongestProjectileKill,
+    FastestWoolCapture,
+    FastestFlagCapture,
+    FastestFirstBlood,
+    KillsInMatch,
+    DeathsInMatch
+}
+
+impl LevelRecordType {
+    // (match id, holder, value) of the current record in the slot, if any
+    pub fn get_current(&self, records: &LevelRecords) -> Option<(String, SimplePlayer, u64)> {
+        match self {
+            LevelRecordType::HighestKillstreak => records.highest_killstreak.as_ref().map(|record| (record.match_id.clone(), record.player.clone(), record.value as u64)),
+            LevelRecordType::LongestProjectileKill => records.longest_projectile_kill.as_ref().map(|record| (record.match_id.clone(), record.player.clone(), record.distance as u64)),
+            LevelRecordType::FastestWoolCapture => records.fastest_wool_capture.as_ref().map(|record| (record.match_id.clone(), record.player.clone(), record.value)),
+            LevelRecordType::FastestFlagCapture => records.fastest_flag_capture.as_ref().map(|record| (record.match_id.clone(), record.player.clone(), record.value)),
+            LevelRecordType::FastestFirstBlood => records.fastest_first_blood.as_ref().map(|record| (record.match_id.clone(), record.attacker.clone(), record.time)),
+            LevelRecordType::KillsInMatch => records.kills_in_match.as_ref().map(|record| (record.match_id.clone(), record.player.clone(), record.value as u64)),
+            LevelRecordType::DeathsInMatch => records.deaths_in_match.as_ref().map(|record| (record.match_id.clone(), record.player.clone(), record.value as u64))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelRecordEntry {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub record_type: LevelRecordType,
+    pub level_id: String,
+    pub holder: SimplePlayer,
+    pub value: u64,
+    pub match_id: String,
+    pub achieved_at: u64,
+    #[serde(default)]
+    pub broken_at: Option<u64>
+}
+
+impl CollectionOwner<LevelRecordEntry> for LevelRecordEntry {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<LevelRecordEntry> {
+        &database.level_records
+    }
+
+    fn get_collection_name() -> &'static str {
+        "level_record"
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelReward {
+    pub level: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub join_sounds: Vec<String>,
+    #[serde(default)]
+    pub ranks: Vec<String>
+}
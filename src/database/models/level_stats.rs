@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Serialize, Deserialize};
+
+use crate::database::CollectionOwner;
+
+use super::level::LevelGamemode;
+
+pub const RECENT_DURATION_COUNT : i32 = 500;
+
+// running totals per map, only ever modified through $inc so concurrent match ends don't clobber each other
+#[derive(Serialize, Deserialize, IdentifiableDocument)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelStats {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(default)]
+    pub times_played: u32,
+    #[serde(default)]
+    pub total_duration: u64,
+    #[serde(default)]
+    pub recent_durations: Vec<u64>,
+    #[serde(default)]
+    pub party_results: HashMap<String, PartyResults>,
+    #[serde(default)]
+    pub ties: u32,
+    #[serde(default)]
+    pub total_participants: u64,
+    #[serde(default)]
+    pub total_kills: u64,
+    #[serde(default)]
+    pub gamemode_plays: HashMap<LevelGamemode, u32>,
+    #[serde(default)]
+    pub last_played_at: Option<u64>
+}
+
+impl LevelStats {
+    pub fn empty(level_id: &String) -> Self {
+        LevelStats {
+            id: level_id.clone(),
+            times_played: 0,
+            total_duration: 0,
+            recent_durations: Vec::new(),
+            party_results: HashMap::new(),
+            ties: 0,
+            total_participants: 0,
+            total_kills: 0,
+            gamemode_plays: HashMap::new(),
+            last_played_at: None
+        }
+    }
+}
+
+impl CollectionOwner<LevelStats> for LevelStats {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<LevelStats> {
+        &database.level_stats
+    }
+
+    fn get_collection_name() -> &'static str {
+        "level_stats"
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PartyResults {
+    #[serde(default)]
+    pub matches: u32,
+    #[serde(default)]
+    pub wins: u32
+}
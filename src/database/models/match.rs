@@ -23,7 +23,10 @@ pub struct Match {
     pub parties: HashMap<String, Party>,
     pub participants: HashMap<String, Participant>,
     pub server_id: String,
-    pub first_blood: Option<FirstBlood>
+    pub first_blood: Option<FirstBlood>,
+    // only recorded for matches ended after results started being persisted
+    #[serde(default)]
+    pub winning_parties: Option<Vec<String>>
 }
 
 impl Match {
@@ -11,3 +11,12 @@ pub mod participant;
 pub mod death;
 pub mod join_sound;
 pub mod server;
+pub mod rank_promotion;
+pub mod level_reward;
+pub mod leaderboard_snapshot;
+pub mod rating;
+pub mod level_stats;
+pub mod level_record;
+pub mod rotation;
+pub mod level_rating;
+pub mod presence;
@@ -6,9 +6,9 @@ use mongodb::Collection;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 
-use crate::{database::CollectionOwner, socket::{leaderboard::ScoreType, player::{player_xp_listener::{PlayerXPListener, XP_PER_LEVEL}, player_events::PlayerXPGainData}, server::server_context::{ServerContext}, event_type::EventType}};
+use crate::{database::CollectionOwner, socket::{leaderboard::ScoreType, player::{player_xp_listener::{PlayerXPListener, XP_PER_LEVEL}, player_events::PlayerXPGainData}, server::server_context::{ServerContext}, event_type::EventType}, util::{time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState};
 
-use super::{punishment::StaffNote, level::LevelGamemode, r#match::Match};
+use super::{punishment::StaffNote, level::LevelGamemode, r#match::Match, rank::{Rank, RankGrant}, rank_promotion::RankPromotion, rating::SkillRating};
 
 #[derive(Debug, Serialize, Deserialize, Clone, IdentifiableDocument)]
 #[serde(rename_all = "camelCase")]
@@ -24,11 +24,27 @@ pub struct Player {
     pub ips: Vec<String>,
     pub notes: Vec<StaffNote>,
     pub rank_ids: Vec<String>,
+    #[serde(default)]
+    pub rank_grants: Vec<RankGrant>,
     pub tag_ids: Vec<String>,
     pub active_tag_id: Option<String>,
     pub stats: PlayerStats,
     pub gamemode_stats: HashMap<LevelGamemode, GamemodeStats>,
-    pub active_join_sound_id: Option<String>
+    #[serde(default)]
+    pub ratings: HashMap<LevelGamemode, SkillRating>,
+    pub active_join_sound_id: Option<String>,
+    #[serde(default)]
+    pub join_sound_ids: Vec<String>,
+    #[serde(default)]
+    pub level_ups: Vec<LevelUpRecord>,
+    // promotion ranks are only ever granted once, so staff can take them away for good
+    #[serde(default)]
+    pub promoted_rank_ids: Vec<String>,
+    // messages earned while the player had no server to show them on, sent on the next login
+    #[serde(default)]
+    pub pending_messages: Vec<String>,
+    #[serde(default)]
+    pub name_history: Vec<PlayerNameHistoryEntry>
 }
 
 impl Player {
@@ -40,14 +56,90 @@ impl Player {
         format!("{}/{}", self.id, self.name)
     }
 
+    // called with the name the player joined with, appends to the history when it changed
+    pub fn observe_name(&mut self, name: &str, time_millis: u64) {
+        if self.name_history.is_empty() {
+            // players from before names were tracked
+            self.name_history.push(PlayerNameHistoryEntry {
+                name: self.name.clone(),
+                name_lower: self.name.to_lowercase(),
+                first_seen_at: self.first_joined_at as u64,
+                last_seen_at: self.last_joined_at as u64
+            });
+        };
+        match self.name_history.last_mut() {
+            Some(latest) if latest.name == name => latest.last_seen_at = time_millis,
+            _ => self.name_history.push(PlayerNameHistoryEntry {
+                name: name.to_owned(),
+                name_lower: name.to_lowercase(),
+                first_seen_at: time_millis,
+                last_seen_at: time_millis
+            })
+        };
+        self.name = name.to_owned();
+        self.name_lower = name.to_lowercase();
+    }
+
     pub fn sanitized_copy(&self) -> Player {
         let mut clone = self.clone();
         clone.ips = Vec::new();
         clone.notes = Vec::new();
         clone.last_session_id = None;
+        clone.pending_messages = Vec::new();
         clone
     }
 
+    pub fn set_rank_grant(&mut self, grant: RankGrant) {
+        if !self.rank_ids.contains(&grant.rank_id) {
+            self.rank_ids.push(grant.rank_id.clone());
+        };
+        self.rank_grants.retain(|existing| existing.rank_id != grant.rank_id);
+        self.rank_grants.push(grant);
+    }
+
+    pub fn remove_rank(&mut self, rank_id: &str) {
+        self.rank_ids.retain(|existing| existing != rank_id);
+        self.rank_grants.retain(|existing| existing.rank_id != rank_id);
+    }
+
+    // removes expired grants along with the rank ids they granted
+    pub fn remove_expired_rank_grants(&mut self) -> Vec<RankGrant> {
+        let (expired, active) : (Vec<RankGrant>, Vec<RankGrant>) = self.rank_grants.drain(..).partition(|grant| grant.is_expired());
+        self.rank_grants = active;
+        for grant in expired.iter() {
+            self.rank_ids.retain(|rank_id| rank_id != &grant.rank_id);
+        };
+        expired
+    }
+
+    // grants every configured promotion rank whose requirements are now met and that wasn't granted before
+    pub async fn apply_rank_promotions(&mut self, state: &MarsAPIState) -> Vec<(Rank, RankPromotion)> {
+        let mut promotions : Vec<(Rank, RankPromotion)> = Vec::new();
+        for promotion in state.config.data.rank_promotions.iter() {
+            if !promotion.is_met_by(self) {
+                continue;
+            };
+            let rank = unwrap_helper::continue_default!(state.database.find_by_id_or_name::<Rank>(&promotion.rank).await);
+            if self.promoted_rank_ids.contains(&rank.id) {
+                continue;
+            };
+            self.promoted_rank_ids.push(rank.id.clone());
+            if self.rank_ids.contains(&rank.id) {
+                continue;
+            };
+            self.set_rank_grant(RankGrant { 
+                rank_id: rank.id.clone(), 
+                granted_at: get_u64_time_millis(), 
+                expires_at: None, 
+                reason: Some(String::from("Automatic promotion")), 
+                granter: None 
+            });
+            info!("Promoted {} to rank '{}'", self.id_name(), rank.name);
+            promotions.push((rank, promotion.clone()));
+        }
+        promotions
+    }
+
     pub async fn modify_gamemode_stats<F, Fut>(
         &mut self, 
         current_match: &Match, 
@@ -59,15 +151,23 @@ impl Player {
         }
     }
 
-    // TODO: Multipliers
     pub async fn add_xp(&mut self, server_context: &mut ServerContext, raw_xp: u32, reason: &String, notify: bool, raw_only: bool) {
         let original_level = self.stats.get_level();
-        let target_xp_increment = if raw_only { raw_xp } else { u32::max(PlayerXPListener::gain(raw_xp, original_level), raw_xp) };
+        let base_xp_increment = if raw_only { raw_xp } else { u32::max(PlayerXPListener::gain(raw_xp, original_level), raw_xp) };
+        let multiplier = server_context.api_state.xp_multipliers.get(&server_context.api_state, &server_context.id).await
+            .map(|xp_multiplier| xp_multiplier.value)
+            .unwrap_or(1f32);
+        let target_xp_increment = ((base_xp_increment as f32) * multiplier).round() as u32;
         self.stats.xp += target_xp_increment;
 
         server_context.call(&EventType::PlayerXpGain, PlayerXPGainData { player_id: self.id.clone(), gain: target_xp_increment, reason: reason.clone(), notify }).await;
 
         server_context.api_state.leaderboards.xp.increment(&self.id_name(), Some(target_xp_increment)).await;
+
+        let new_level = self.stats.get_level();
+        if new_level > original_level {
+            PlayerXPListener::on_level_up(server_context, self, original_level, new_level).await;
+        };
     }
 }
 
@@ -268,6 +368,35 @@ pub struct PlayerRecord<T> {
     pub value: T
 }
 
+// the few fields player search needs, loaded with a projection
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSearchDocument {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub name_lower: String,
+    pub last_joined_at: f64,
+    #[serde(default)]
+    pub name_history: Vec<PlayerNameHistoryEntry>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerNameHistoryEntry {
+    pub name: String,
+    pub name_lower: String,
+    pub first_seen_at: u64,
+    pub last_seen_at: u64
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelUpRecord {
+    pub level: u32,
+    pub reached_at: u64
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionRecord {
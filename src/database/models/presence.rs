@@ -0,0 +1,131 @@
+use redis::aio::Connection;
+use rocket::serde::json::serde_json;
+use serde::{Serialize, Deserialize};
+
+use crate::{MarsAPIState, util::r#macro::unwrap_helper};
+
+use super::player::SimplePlayer;
+
+// a server's online list and its players' pointers expire when nothing has been heard from it for this long
+pub const SERVER_PRESENCE_TTL_MS : u64 = 600_000;
+// how often a connected server's presence ttl is pushed back
+pub const SERVER_PRESENCE_REFRESH_MS : u64 = 60_000;
+const ONLINE_SERVERS_KEY : &'static str = "online:servers";
+// pushes back the server list and every pointer into it in one round trip
+const REFRESH_SERVER_SCRIPT : &'static str = r#"
+redis.call('PEXPIRE', KEYS[1], ARGV[1])
+for _, player_id in ipairs(redis.call('HKEYS', KEYS[1])) do
+    redis.call('PEXPIRE', 'online:player:' .. player_id, ARGV[1])
+end
+"#;
+
+// kept in redis only: player -> server pointers plus a hash of players per server
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPresence {
+    pub player: SimplePlayer,
+    pub server_id: String,
+    pub session_id: String,
+    pub joined_at: u64
+}
+
+impl PlayerPresence {
+    fn get_player_key(player_id: &str) -> String {
+        format!("online:player:{}", player_id)
+    }
+
+    fn get_server_key(server_id: &str) -> String {
+        format!("server:{}:online", server_id)
+    }
+
+    pub async fn set_online(&self, state: &MarsAPIState) {
+        let serialized = unwrap_helper::result_return_default!(serde_json::to_string(self), ());
+        let player_key = Self::get_player_key(&self.player.id);
+        let server_key = Self::get_server_key(&self.server_id);
+        let player_id = self.player.id.clone();
+        let server_id = self.server_id.clone();
+        let _ = state.redis.submit(|mut conn| async move {
+            let _ = redis::pipe().atomic()
+                .cmd("SET").arg(&player_key).arg(&server_id).arg("PX").arg(SERVER_PRESENCE_TTL_MS).ignore()
+                .cmd("HSET").arg(&server_key).arg(&player_id).arg(&serialized).ignore()
+                .cmd("PEXPIRE").arg(&server_key).arg(SERVER_PRESENCE_TTL_MS).ignore()
+                .cmd("SADD").arg(ONLINE_SERVERS_KEY).arg(&server_id).ignore()
+                .query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+
+    // a logout from a session the player already left (e.g. a closed concurrent session) is ignored
+    pub async fn set_offline(state: &MarsAPIState, player_id: &str, session_id: &str) {
+        let presence = unwrap_helper::return_default!(Self::get(state, player_id).await, ());
+        if presence.session_id != session_id {
+            return;
+        };
+        let player_key = Self::get_player_key(player_id);
+        let server_key = Self::get_server_key(&presence.server_id);
+        let _ = state.redis.submit(|mut conn| async move {
+            let _ = redis::pipe().atomic()
+                .cmd("DEL").arg(&player_key).ignore()
+                .cmd("HDEL").arg(&server_key).arg(player_id).ignore()
+                .query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+
+    // None when offline or when the server the player was on stopped responding
+    pub async fn get(state: &MarsAPIState, player_id: &str) -> Option<PlayerPresence> {
+        let player_key = Self::get_player_key(player_id);
+        let server_id = state.redis.submit(|mut conn| async move {
+            redis::cmd("GET").arg(&player_key).query_async::<Connection, Option<String>>(&mut conn).await.ok().flatten()
+        }).await.ok().flatten()?;
+        let server_key = Self::get_server_key(&server_id);
+        let serialized = state.redis.submit(|mut conn| async move {
+            redis::cmd("HGET").arg(&server_key).arg(player_id).query_async::<Connection, Option<String>>(&mut conn).await.ok().flatten()
+        }).await.ok().flatten()?;
+        serde_json::from_str(&serialized).ok()
+    }
+
+    pub async fn get_server_players(state: &MarsAPIState, server_id: &str) -> Vec<PlayerPresence> {
+        let server_key = Self::get_server_key(server_id);
+        let serialized = state.redis.submit(|mut conn| async move {
+            redis::cmd("HVALS").arg(&server_key).query_async::<Connection, Vec<String>>(&mut conn).await.unwrap_or(Vec::new())
+        }).await.unwrap_or(Vec::new());
+        serialized.iter().filter_map(|presence| serde_json::from_str(presence).ok()).collect()
+    }
+
+    // servers whose online list expired are dropped along the way
+    pub async fn get_online_servers(state: &MarsAPIState) -> Vec<String> {
+        let server_ids = state.redis.submit(|mut conn| async move {
+            redis::cmd("SMEMBERS").arg(ONLINE_SERVERS_KEY).query_async::<Connection, Vec<String>>(&mut conn).await.unwrap_or(Vec::new())
+        }).await.unwrap_or(Vec::new());
+        let mut online_server_ids : Vec<String> = Vec::new();
+        for server_id in server_ids.into_iter() {
+            let server_key = Self::get_server_key(&server_id);
+            let exists = state.redis.submit(|mut conn| async move {
+                redis::cmd("EXISTS").arg(&server_key).query_async::<Connection, bool>(&mut conn).await.unwrap_or(false)
+            }).await.unwrap_or(false);
+            if exists {
+                online_server_ids.push(server_id);
+            } else {
+                let _ = state.redis.submit(|mut conn| async move {
+                    let _ = redis::cmd("SREM").arg(ONLINE_SERVERS_KEY).arg(&server_id).query_async::<Connection, ()>(&mut conn).await;
+                }).await;
+            };
+        };
+        online_server_ids
+    }
+
+    // called at most every SERVER_PRESENCE_REFRESH_MS while the server is heard from
+    pub async fn refresh_server(state: &MarsAPIState, server_id: &str) {
+        let server_key = Self::get_server_key(server_id);
+        let _ = state.redis.submit(|mut conn| async move {
+            let _ = redis::cmd("EVAL").arg(REFRESH_SERVER_SCRIPT).arg(1).arg(&server_key).arg(SERVER_PRESENCE_TTL_MS)
+                .query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+
+    pub async fn clear_server(state: &MarsAPIState, server_id: &str) {
+        let server_key = Self::get_server_key(server_id);
+        let _ = state.redis.submit(|mut conn| async move {
+            let _ = redis::cmd("DEL").arg(&server_key).query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+}
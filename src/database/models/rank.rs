@@ -1,11 +1,15 @@
 use mars_api_rs_derive::IdentifiableDocument;
 use mars_api_rs_macro::IdentifiableDocument;
+use std::collections::{HashMap, HashSet};
+
 use mongodb::bson::doc;
 use serde::{Serialize, Deserialize};
 
-use crate::database::{CollectionOwner, Database};
+use crate::{database::{CollectionOwner, Database}, util::time::get_u64_time_millis};
+
+use super::player::SimplePlayer;
 
-#[derive(Deserialize, Serialize, Debug, IdentifiableDocument)]
+#[derive(Deserialize, Serialize, Debug, Clone, IdentifiableDocument)]
 #[serde(rename_all = "camelCase")]
 pub struct Rank {
     #[id]
@@ -21,7 +25,9 @@ pub struct Rank {
     pub permissions: Vec<String>,
     pub staff: bool,
     pub apply_on_join: bool,
-    pub created_at: f64
+    pub created_at: f64,
+    #[serde(default)]
+    pub inherits: Vec<String>
 }
 
 impl CollectionOwner<Rank> for Rank {
@@ -44,4 +50,141 @@ impl Rank {
         };
         Database::consume_cursor_into_owning_vec(cursor).await
     }
+
+    // checks whether giving `rank_id` the parents in `inherits` would let it reach itself
+    pub fn creates_inheritance_cycle(ranks: &Vec<Rank>, rank_id: &str, inherits: &Vec<String>) -> bool {
+        let graph : HashMap<&str, &Vec<String>> = ranks.iter().map(|rank| (rank.id.as_str(), &rank.inherits)).collect();
+        let mut visited : HashSet<&str> = HashSet::new();
+        let mut pending : Vec<&str> = inherits.iter().map(|id| id.as_str()).collect();
+        while let Some(current) = pending.pop() {
+            if current == rank_id {
+                return true;
+            };
+            if !visited.insert(current) {
+                continue;
+            };
+            if let Some(parents) = graph.get(current) {
+                pending.extend(parents.iter().map(|id| id.as_str()));
+            };
+        }
+        false
+    }
+
+    // the player's own ranks plus those applied on join, with everything they inherit
+    pub fn resolve_effective<'a>(ranks: &'a Vec<Rank>, player_rank_ids: &Vec<String>) -> Vec<&'a Rank> {
+        let mut held_rank_ids = player_rank_ids.clone();
+        held_rank_ids.extend(ranks.iter().filter(|rank| rank.apply_on_join).map(|rank| rank.id.clone()));
+        Self::resolve_inherited(ranks, &held_rank_ids)
+    }
+
+    // expands `rank_ids` with every rank they inherit from, directly or transitively
+    pub fn resolve_inherited<'a>(ranks: &'a Vec<Rank>, rank_ids: &Vec<String>) -> Vec<&'a Rank> {
+        let by_id : HashMap<&str, &Rank> = ranks.iter().map(|rank| (rank.id.as_str(), rank)).collect();
+        let mut visited : HashSet<&str> = HashSet::new();
+        let mut resolved : Vec<&Rank> = Vec::new();
+        let mut pending : Vec<&str> = rank_ids.iter().map(|id| id.as_str()).collect();
+        while let Some(current) = pending.pop() {
+            if !visited.insert(current) {
+                continue;
+            };
+            if let Some(rank) = by_id.get(current) {
+                resolved.push(rank);
+                pending.extend(rank.inherits.iter().map(|id| id.as_str()));
+            };
+        }
+        resolved
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RankGrant {
+    pub rank_id: String,
+    pub granted_at: u64,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub granter: Option<SimplePlayer>
+}
+
+impl RankGrant {
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at <= get_u64_time_millis(),
+            None => false
+        }
+    }
+
+    pub fn time_remaining(&self) -> Option<u64> {
+        self.expires_at.map(|expires_at| expires_at.saturating_sub(get_u64_time_millis()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rank;
+
+    fn rank(id: &str, inherits: &[&str]) -> Rank {
+        Rank {
+            id: id.to_string(),
+            name: id.to_string(),
+            name_lower: id.to_lowercase(),
+            display_name: None,
+            prefix: None,
+            priority: 0,
+            permissions: vec![format!("{}.perm", id)],
+            staff: false,
+            apply_on_join: false,
+            created_at: 0.0,
+            inherits: inherits.iter().map(|id| id.to_string()).collect()
+        }
+    }
+
+    fn ids(rank_ids: &[&str]) -> Vec<String> {
+        rank_ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn inheriting_itself_is_a_cycle() {
+        let ranks = vec![rank("a", &[])];
+        assert!(Rank::creates_inheritance_cycle(&ranks, "a", &ids(&["a"])));
+    }
+
+    #[test]
+    fn inheriting_a_descendant_is_a_cycle() {
+        // c -> b -> a, so a can't inherit c
+        let ranks = vec![rank("a", &[]), rank("b", &["a"]), rank("c", &["b"])];
+        assert!(Rank::creates_inheritance_cycle(&ranks, "a", &ids(&["b"])));
+        assert!(Rank::creates_inheritance_cycle(&ranks, "a", &ids(&["c"])));
+    }
+
+    #[test]
+    fn shared_ancestors_are_not_a_cycle() {
+        let ranks = vec![rank("a", &[]), rank("b", &["a"]), rank("c", &["a"]), rank("d", &[])];
+        assert!(!Rank::creates_inheritance_cycle(&ranks, "d", &ids(&["b", "c"])));
+        assert!(!Rank::creates_inheritance_cycle(&ranks, "c", &ids(&["b"])));
+        // unknown parents are left to validation elsewhere
+        assert!(!Rank::creates_inheritance_cycle(&ranks, "d", &ids(&["missing"])));
+    }
+
+    #[test]
+    fn existing_cycles_do_not_loop_forever() {
+        let ranks = vec![rank("a", &["b"]), rank("b", &["a"]), rank("c", &[])];
+        assert!(!Rank::creates_inheritance_cycle(&ranks, "c", &ids(&["a"])));
+        let mut resolved : Vec<&str> = Rank::resolve_inherited(&ranks, &ids(&["a"])).iter().map(|rank| rank.id.as_str()).collect();
+        resolved.sort();
+        assert_eq!(resolved, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn effective_ranks_include_join_ranks_and_their_parents() {
+        let mut default = rank("default", &["base"]);
+        default.apply_on_join = true;
+        let ranks = vec![rank("base", &[]), default, rank("vip", &["base"]), rank("mod", &["vip"])];
+        let mut resolved : Vec<&str> = Rank::resolve_effective(&ranks, &ids(&["mod"])).iter().map(|rank| rank.id.as_str()).collect();
+        resolved.sort();
+        assert_eq!(resolved, vec!["base", "default", "mod", "vip"]);
+    }
 }
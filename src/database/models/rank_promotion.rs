@@ -0,0 +1,35 @@
+use serde::{Serialize, Deserialize};
+
+use crate::socket::leaderboard::ScoreType;
+
+use super::{player::Player, rank::Rank};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RankPromotion {
+    // id or name of the rank to grant
+    pub rank: String,
+    #[serde(default)]
+    pub level: Option<u32>,
+    #[serde(default)]
+    pub stat: Option<ScoreType>,
+    #[serde(default)]
+    pub amount: u32,
+    #[serde(default)]
+    pub message: Option<String>
+}
+
+impl RankPromotion {
+    pub fn is_met_by(&self, player: &Player) -> bool {
+        if self.level.is_none() && self.stat.is_none() {
+            return false;
+        };
+        let level_met = self.level.map_or(true, |level| player.stats.get_level() >= level);
+        let stat_met = self.stat.as_ref().map_or(true, |stat| player.stats.get_score(stat) >= self.amount);
+        level_met && stat_met
+    }
+
+    pub fn get_message(&self, rank: &Rank) -> String {
+        self.message.clone().unwrap_or_else(|| format!("You have been promoted to {}!", rank.display_name.as_ref().unwrap_or(&rank.name)))
+    }
+}
@@ -0,0 +1,49 @@
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use serde::{Serialize, Deserialize};
+
+use crate::{database::CollectionOwner, socket::participant::participant_context::PlayerMatchResult};
+
+use super::{player::SimplePlayer, level::LevelGamemode};
+
+pub const DEFAULT_RATING : f64 = 1500.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillRating {
+    pub rating: f64,
+    pub peak_rating: f64,
+    pub matches: u32,
+    pub updated_at: u64
+}
+
+impl Default for SkillRating {
+    fn default() -> Self {
+        SkillRating { rating: DEFAULT_RATING, peak_rating: DEFAULT_RATING, matches: 0, updated_at: 0 }
+    }
+}
+
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RatingChange {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub player: SimplePlayer,
+    pub gamemode: LevelGamemode,
+    pub match_id: String,
+    pub result: PlayerMatchResult,
+    pub previous_rating: f64,
+    pub rating: f64,
+    pub created_at: u64
+}
+
+impl CollectionOwner<RatingChange> for RatingChange {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<RatingChange> {
+        &database.rating_changes
+    }
+
+    fn get_collection_name() -> &'static str {
+        "rating_change"
+    }
+}
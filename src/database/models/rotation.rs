@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
+use rand::{distributions::WeightedIndex, prelude::Distribution};
+use redis::aio::Connection;
+use serde::{Serialize, Deserialize};
+
+use crate::{database::CollectionOwner, MarsAPIState, util::time::get_u64_time_millis};
+
+pub const DEFAULT_RECENT_EXCLUSION : u32 = 3;
+// how long a finished vote is kept around for the server to collect the winner
+pub const VOTE_SESSION_GRACE_MS : u64 = 300_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RotationKind {
+    Ordered,
+    Weighted
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationMap {
+    pub map_id: String,
+    // only used by weighted rotations
+    #[serde(default = "default_weight")]
+    pub weight: u32
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_recent_exclusion() -> u32 {
+    DEFAULT_RECENT_EXCLUSION
+}
+
+#[derive(Debug, Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotation {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub name_lower: String,
+    pub kind: RotationKind,
+    pub maps: Vec<RotationMap>,
+    // applies to every server when empty
+    #[serde(default)]
+    pub server_ids: Vec<String>,
+    #[serde(default)]
+    pub min_players: u32,
+    #[serde(default)]
+    pub max_players: Option<u32>,
+    // how many of the server's last played maps are skipped when picking
+    #[serde(default = "default_recent_exclusion")]
+    pub recent_exclusion: u32,
+    pub created_at: u64,
+    pub updated_at: u64
+}
+
+impl Rotation {
+    // server ids are stored lowercased, the one a server sends may not be
+    pub fn applies_to(&self, server_id: &str, players: u32) -> bool {
+        let server_id = server_id.to_lowercase();
+        (self.server_ids.is_empty() || self.server_ids.iter().any(|id| *id == server_id))
+            && players >= self.min_players
+            && self.max_players.map_or(true, |max_players| players <= max_players)
+    }
+
+    // server specific rotations beat network-wide ones, then the tightest bracket wins
+    pub fn select(rotations: Vec<Rotation>, server_id: &str, players: u32) -> Option<Rotation> {
+        rotations.into_iter()
+            .filter(|rotation| rotation.applies_to(server_id, players))
+            .max_by_key(|rotation| (!rotation.server_ids.is_empty(), rotation.min_players))
+    }
+
+    // falls back to every map when the rotation is smaller than the exclusion window
+    pub fn get_candidates(&self, recent_map_ids: &Vec<String>) -> Vec<&RotationMap> {
+        let candidates : Vec<&RotationMap> = self.maps.iter().filter(|map| !recent_map_ids.contains(&map.map_id)).collect();
+        if candidates.is_empty() { self.maps.iter().collect() } else { candidates }
+    }
+
+    // returns the picked map and, for ordered rotations, the position to continue from
+    pub fn pick_next(&self, position: usize, recent_map_ids: &Vec<String>) -> Option<(RotationMap, usize)> {
+        if self.maps.is_empty() {
+            return None;
+        };
+        match self.kind {
+            RotationKind::Ordered => {
+                let length = self.maps.len();
+                let offset = (0..length).find(|offset| {
+                    !recent_map_ids.contains(&self.maps[(position + offset) % length].map_id)
+                }).unwrap_or(0);
+                let index = (position + offset) % length;
+                Some((self.maps[index].clone(), (index + 1) % length))
+            },
+            RotationKind::Weighted => {
+                let candidates = self.get_candidates(recent_map_ids);
+                let distribution = WeightedIndex::new(candidates.iter().map(|map| map.weight.max(1))).ok()?;
+                Some(((*candidates[distribution.sample(&mut rand::thread_rng())]).clone(), position))
+            }
+        }
+    }
+}
+
+impl CollectionOwner<Rotation> for Rotation {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<Rotation> {
+        &database.rotations
+    }
+
+    fn get_collection_name() -> &'static str {
+        "rotation"
+    }
+}
+
+// where an ordered rotation continues from on a server
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerRotationPosition {
+    pub rotation_id: String,
+    pub position: usize
+}
+
+impl ServerRotationPosition {
+    pub fn get_key(server_id: &str) -> String {
+        format!("server:{}:rotation_position", server_id)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MapVoteOption {
+    pub map_id: String,
+    pub name: String
+}
+
+// lives in redis, ballots are kept in a separate hash so concurrent votes don't overwrite each other
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MapVoteSession {
+    pub id: String,
+    pub server_id: String,
+    pub options: Vec<MapVoteOption>,
+    pub started_at: u64,
+    pub ends_at: u64
+}
+
+impl MapVoteSession {
+    pub fn get_key(server_id: &str) -> String {
+        format!("server:{}:vote", server_id)
+    }
+
+    pub fn get_ballots_key(server_id: &str) -> String {
+        format!("server:{}:vote:ballots", server_id)
+    }
+
+    pub async fn get(state: &MarsAPIState, server_id: &str) -> Option<MapVoteSession> {
+        state.redis.get_unchecked(&Self::get_key(server_id)).await
+    }
+
+    // a player voting again replaces their previous ballot
+    pub async fn cast(&self, state: &MarsAPIState, player_id: &str, map_id: &str) {
+        let key = Self::get_ballots_key(&self.server_id);
+        let expiry_ms = self.ends_at.saturating_sub(get_u64_time_millis()) + VOTE_SESSION_GRACE_MS;
+        let _ = state.redis.submit(|mut conn| async move {
+            let _ = redis::pipe().atomic()
+                .cmd("HSET").arg(&key).arg(player_id).arg(map_id).ignore()
+                .cmd("PEXPIRE").arg(&key).arg(expiry_ms).ignore()
+                .query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+
+    pub async fn get_tallies(&self, state: &MarsAPIState) -> HashMap<String, u32> {
+        let key = Self::get_ballots_key(&self.server_id);
+        let ballots = state.redis.submit(|mut conn| async move {
+            redis::cmd("HVALS").arg(&key).query_async::<Connection, Vec<String>>(&mut conn).await.unwrap_or(Vec::new())
+        }).await.unwrap_or(Vec::new());
+        let mut tallies : HashMap<String, u32> = self.options.iter().map(|option| (option.map_id.clone(), 0)).collect();
+        for map_id in ballots.into_iter() {
+            if let Some(votes) = tallies.get_mut(&map_id) {
+                *votes += 1;
+            };
+        };
+        tallies
+    }
+
+    pub async fn clear(&self, state: &MarsAPIState) {
+        let key = Self::get_key(&self.server_id);
+        let ballots_key = Self::get_ballots_key(&self.server_id);
+        let _ = state.redis.submit(|mut conn| async move {
+            let _ = redis::cmd("DEL").arg(&key).arg(&ballots_key).query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rotation, RotationKind, RotationMap};
+
+    fn rotation(kind: RotationKind, map_ids: &[&str]) -> Rotation {
+        Rotation {
+            id: String::from("rotation"),
+            name: String::from("Rotation"),
+            name_lower: String::from("rotation"),
+            kind,
+            maps: map_ids.iter().map(|map_id| RotationMap { map_id: map_id.to_string(), weight: 1 }).collect(),
+            server_ids: Vec::new(),
+            min_players: 0,
+            max_players: None,
+            recent_exclusion: 3,
+            created_at: 0,
+            updated_at: 0
+        }
+    }
+
+    fn recent(map_ids: &[&str]) -> Vec<String> {
+        map_ids.iter().map(|map_id| map_id.to_string()).collect()
+    }
+
+    #[test]
+    fn ordered_rotation_advances_and_wraps() {
+        let rotation = rotation(RotationKind::Ordered, &["a", "b", "c"]);
+        let (map, position) = rotation.pick_next(0, &Vec::new()).unwrap();
+        assert_eq!((map.map_id.as_str(), position), ("a", 1));
+        let (map, position) = rotation.pick_next(2, &Vec::new()).unwrap();
+        assert_eq!((map.map_id.as_str(), position), ("c", 0));
+        // positions left over from a longer rotation wrap around
+        let (map, position) = rotation.pick_next(4, &Vec::new()).unwrap();
+        assert_eq!((map.map_id.as_str(), position), ("b", 2));
+    }
+
+    #[test]
+    fn ordered_rotation_skips_recent_maps() {
+        let rotation = rotation(RotationKind::Ordered, &["a", "b", "c"]);
+        let (map, position) = rotation.pick_next(0, &recent(&["a", "b"])).unwrap();
+        assert_eq!((map.map_id.as_str(), position), ("c", 0));
+        // every map was played recently, so the rotation carries on in order
+        let (map, position) = rotation.pick_next(1, &recent(&["a", "b", "c"])).unwrap();
+        assert_eq!((map.map_id.as_str(), position), ("b", 2));
+    }
+
+    #[test]
+    fn weighted_rotation_only_picks_candidates() {
+        let rotation = rotation(RotationKind::Weighted, &["a", "b", "c"]);
+        for _ in 0..50 {
+            let (map, position) = rotation.pick_next(7, &recent(&["a", "c"])).unwrap();
+            assert_eq!((map.map_id.as_str(), position), ("b", 7));
+        };
+        let candidates : Vec<&str> = rotation.get_candidates(&recent(&["a", "b", "c"])).iter().map(|map| map.map_id.as_str()).collect();
+        assert_eq!(candidates, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn empty_rotation_picks_nothing() {
+        assert!(rotation(RotationKind::Ordered, &[]).pick_next(0, &Vec::new()).is_none());
+        assert!(rotation(RotationKind::Weighted, &[]).pick_next(0, &Vec::new()).is_none());
+    }
+
+    #[test]
+    fn server_specific_rotation_is_selected_first() {
+        let network = rotation(RotationKind::Ordered, &["a"]);
+        let mut server = rotation(RotationKind::Ordered, &["b"]);
+        server.id = String::from("server");
+        server.server_ids = vec![String::from("lobby")];
+        let mut crowded = rotation(RotationKind::Ordered, &["c"]);
+        crowded.id = String::from("crowded");
+        crowded.min_players = 20;
+        assert_eq!(Rotation::select(vec![network.clone(), server.clone()], "lobby", 5).unwrap().id, "server");
+        assert_eq!(Rotation::select(vec![network.clone(), server.clone()], "Lobby", 5).unwrap().id, "server");
+        assert_eq!(Rotation::select(vec![network.clone(), server.clone()], "other", 5).unwrap().id, "rotation");
+        assert_eq!(Rotation::select(vec![network.clone(), crowded.clone()], "other", 25).unwrap().id, "crowded");
+        assert_eq!(Rotation::select(vec![network, crowded], "other", 5).unwrap().id, "rotation");
+    }
+}
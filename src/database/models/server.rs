@@ -1,4 +1,11 @@
+use std::collections::HashMap;
+
+use mars_api_rs_derive::IdentifiableDocument;
+use mars_api_rs_macro::IdentifiableDocument;
 use serde::{Serialize, Deserialize};
+use tokio::sync::Mutex;
+
+use crate::{database::CollectionOwner, MarsAPIState, util::time::get_u64_time_millis};
 
 use super::player::SimplePlayer;
 
@@ -8,10 +15,95 @@ pub struct ServerEvents {
     pub xp_multiplier: Option<XPMultiplier>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct XPMultiplier {
     pub value: f32,
     pub player: Option<SimplePlayer>,
     pub updated_at: u64
 }
+
+impl ServerEvents {
+    pub async fn get(state: &MarsAPIState, server_id: &str) -> ServerEvents {
+        state.redis.get_unchecked(&format!("server:{}:events", server_id)).await.unwrap_or(ServerEvents { 
+            xp_multiplier: None  
+        })
+    }
+
+    // the highest of the server's own multiplier and any scheduled window covering the server wins
+    pub async fn get_active_xp_multiplier(state: &MarsAPIState, server_id: &str) -> Option<XPMultiplier> {
+        let events = Self::get(state, server_id).await;
+        let scheduled = state.database.get_active_xp_multiplier_windows(server_id).await
+            .into_iter()
+            .map(|window| window.to_xp_multiplier());
+        events.xp_multiplier.into_iter().chain(scheduled).fold(None, |highest : Option<XPMultiplier>, multiplier| {
+            match highest {
+                Some(highest) if highest.value >= multiplier.value => Some(highest),
+                _ => Some(multiplier)
+            }
+        })
+    }
+}
+
+const XP_MULTIPLIER_CACHE_TTL_MS : u64 = 15_000;
+
+// xp is gained on every kill and objective, so the active multiplier is only looked up every few seconds per server.
+// changes made through this instance are picked up right away, other instances' within the ttl
+pub struct XPMultiplierCache {
+    entries: Mutex<HashMap<String, (u64, Option<XPMultiplier>)>>
+}
+
+impl XPMultiplierCache {
+    pub fn new() -> Self {
+        XPMultiplierCache { entries: Mutex::new(HashMap::new()) }
+    }
+
+    pub async fn get(&self, state: &MarsAPIState, server_id: &str) -> Option<XPMultiplier> {
+        let time_millis = get_u64_time_millis();
+        if let Some((fetched_at, xp_multiplier)) = self.entries.lock().await.get(server_id) {
+            if time_millis < fetched_at + XP_MULTIPLIER_CACHE_TTL_MS {
+                return xp_multiplier.clone();
+            };
+        };
+        let xp_multiplier = ServerEvents::get_active_xp_multiplier(state, server_id).await;
+        self.entries.lock().await.insert(server_id.to_owned(), (time_millis, xp_multiplier.clone()));
+        xp_multiplier
+    }
+
+    pub async fn invalidate(&self) {
+        self.entries.lock().await.clear();
+    }
+}
+
+#[derive(Serialize, Deserialize, IdentifiableDocument, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct XPMultiplierWindow {
+    #[id]
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub value: f32,
+    // applies network-wide when absent
+    #[serde(default)]
+    pub server_id: Option<String>,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    #[serde(default)]
+    pub player: Option<SimplePlayer>,
+    pub created_at: u64
+}
+
+impl XPMultiplierWindow {
+    pub fn to_xp_multiplier(&self) -> XPMultiplier {
+        XPMultiplier { value: self.value, player: self.player.clone(), updated_at: self.starts_at }
+    }
+}
+
+impl CollectionOwner<XPMultiplierWindow> for XPMultiplierWindow {
+    fn get_collection(database: &crate::database::Database) -> &mongodb::Collection<XPMultiplierWindow> {
+        &database.xp_multiplier_windows
+    }
+
+    fn get_collection_name() -> &'static str {
+        "xp_multiplier_window"
+    }
+}
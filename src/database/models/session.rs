@@ -7,6 +7,25 @@ use crate::database::CollectionOwner;
 
 use super::player::SimplePlayer;
 
+// most playtime credited for a session closed by a newer login, a ghost session can't be verified past this
+pub const CONCURRENT_SESSION_MAX_CREDIT_MS : u64 = 21_600_000;
+
+// what login does with a session that's still open, e.g. after a proxy glitch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum ConcurrentSessionPolicy {
+    // end the old session and disconnect the player from its server
+    Close,
+    // refuse the new login until the old session ends
+    Reject
+}
+
+impl Default for ConcurrentSessionPolicy {
+    fn default() -> Self {
+        ConcurrentSessionPolicy::Close
+    }
+}
+
 #[derive(Deserialize, Serialize, IdentifiableDocument, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Session {
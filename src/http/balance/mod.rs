@@ -0,0 +1,139 @@
+use futures::future::join_all;
+use mongodb::bson::doc;
+use rocket::{Rocket, Build, State, serde::json::Json};
+
+use crate::{MarsAPIState, database::{models::{player::{Player, SimplePlayer}, level::LevelGamemode, rating::DEFAULT_RATING}, Database}, util::{auth::AuthorizationToken, error::ApiErrorResponder}};
+
+use self::payload::{TeamBalanceRequest, TeamBalanceResponse, BalancedParty};
+
+mod payload;
+
+const MAX_SWAP_PASSES : u32 = 50;
+
+// rating when the player has been rated in the gamemode, otherwise an estimate on the same scale from lifetime stats
+fn get_skill(player: &Player, gamemode: &Option<LevelGamemode>) -> f64 {
+    if let Some(rating) = gamemode.as_ref().and_then(|gamemode| player.ratings.get(gamemode)) {
+        if rating.matches > 0 {
+            return rating.rating;
+        };
+    };
+    let stats = &player.stats;
+    if stats.matches == 0 {
+        return DEFAULT_RATING;
+    };
+    let kill_death_ratio = (stats.kills as f64 / stats.deaths.max(1) as f64).min(3.0);
+    let win_rate = stats.wins as f64 / stats.matches as f64;
+    DEFAULT_RATING + 100.0 * (kill_death_ratio - 1.0) + 400.0 * (win_rate - 0.5)
+}
+
+fn get_average(skills: &Vec<f64>) -> f64 {
+    if skills.is_empty() { 0.0 } else { skills.iter().sum::<f64>() / skills.len() as f64 }
+}
+
+fn get_party_skills(party_players: &Vec<Vec<(SimplePlayer, f64)>>) -> Vec<Vec<f64>> {
+    party_players.iter().map(|members| members.iter().map(|member| member.1).collect()).collect()
+}
+
+fn get_spread(party_skills: &Vec<Vec<f64>>) -> f64 {
+    let averages : Vec<f64> = party_skills.iter().filter(|skills| !skills.is_empty()).map(get_average).collect();
+    let highest = averages.iter().cloned().fold(f64::MIN, f64::max);
+    let lowest = averages.iter().cloned().fold(f64::MAX, f64::min);
+    if averages.is_empty() { 0.0 } else { highest - lowest }
+}
+
+#[post("/", format = "json", data = "<balance_req>")]
+async fn balance_teams(
+    state: &State<MarsAPIState>,
+    balance_req: Json<TeamBalanceRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<Json<TeamBalanceResponse>, ApiErrorResponder> {
+    let data = balance_req.0;
+    if data.parties.is_empty() {
+        return Err(ApiErrorResponder::validation_error_with_message("At least one party is required"));
+    };
+    if data.parties.iter().any(|party| party.min > party.max) {
+        return Err(ApiErrorResponder::validation_error_with_message("A party's minimum can't exceed its maximum"));
+    };
+    let mut player_ids = data.player_ids.clone();
+    player_ids.sort();
+    player_ids.dedup();
+
+    let stored_players = Database::consume_cursor_into_owning_vec_option(state.database.players.find(doc! { "_id": { "$in": &player_ids } }, None).await.ok()).await;
+    if stored_players.len() != player_ids.len() {
+        return Err(ApiErrorResponder::missing_player());
+    };
+    // the cache is keyed by name and holds stats from a running match that aren't saved yet
+    let lookups : Vec<_> = stored_players.iter().map(|player| state.player_cache.query(&player.name)).collect();
+    let mut players : Vec<(SimplePlayer, f64)> = Vec::new();
+    for (stored_player, cached_player) in stored_players.iter().zip(join_all(lookups).await.into_iter()) {
+        let player = cached_player.as_ref().unwrap_or(stored_player);
+        players.push((player.to_simple(), get_skill(player, &data.gamemode)));
+    };
+    players.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    // strongest players first, each going to a party still short of its minimum, then the emptiest and then the weakest one
+    let mut party_players : Vec<Vec<(SimplePlayer, f64)>> = data.parties.iter().map(|_| Vec::new()).collect();
+    let mut unassigned : Vec<SimplePlayer> = Vec::new();
+    for (player, skill) in players.into_iter() {
+        let target = party_players.iter().enumerate()
+            .filter(|(index, members)| (members.len() as u32) < data.parties[*index].max)
+            .min_by(|(a_index, a), (b_index, b)| {
+                let a_filled = a.len() as u32 >= data.parties[*a_index].min;
+                let b_filled = b.len() as u32 >= data.parties[*b_index].min;
+                let a_total : f64 = a.iter().map(|member| member.1).sum();
+                let b_total : f64 = b.iter().map(|member| member.1).sum();
+                a_filled.cmp(&b_filled)
+                    .then(a.len().cmp(&b.len()))
+                    .then(a_total.partial_cmp(&b_total).unwrap_or(std::cmp::Ordering::Equal))
+            })
+            .map(|(index, _)| index);
+        match target {
+            Some(index) => party_players[index].push((player, skill)),
+            None => unassigned.push(player)
+        };
+    };
+
+    // then swap pairs across parties while it narrows the gap between the strongest and weakest party
+    for _ in 0..MAX_SWAP_PASSES {
+        let mut improved = false;
+        for first in 0..party_players.len() {
+            for second in (first + 1)..party_players.len() {
+                for i in 0..party_players[first].len() {
+                    for j in 0..party_players[second].len() {
+                        let mut swapped = get_party_skills(&party_players);
+                        let current_spread = get_spread(&swapped);
+                        let first_skill = swapped[first][i];
+                        swapped[first][i] = swapped[second][j];
+                        swapped[second][j] = first_skill;
+                        if get_spread(&swapped) + f64::EPSILON < current_spread {
+                            let first_member = party_players[first][i].clone();
+                            party_players[first][i] = party_players[second][j].clone();
+                            party_players[second][j] = first_member;
+                            improved = true;
+                        };
+                    };
+                };
+            };
+        };
+        if !improved {
+            break;
+        };
+    };
+
+    let skill_difference = get_spread(&get_party_skills(&party_players));
+    let parties = data.parties.iter().zip(party_players.into_iter()).map(|(party, members)| {
+        let skills : Vec<f64> = members.iter().map(|member| member.1).collect();
+        BalancedParty { 
+            name: party.name.clone(), 
+            average_skill: get_average(&skills), 
+            below_min: (members.len() as u32) < party.min,
+            players: members.into_iter().map(|member| member.0).collect() 
+        }
+    }).collect::<Vec<BalancedParty>>();
+    let meets_minimums = parties.iter().all(|party| !party.below_min);
+    Ok(Json(TeamBalanceResponse { parties, unassigned, skill_difference, meets_minimums }))
+}
+
+pub fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
+    rocket.mount("/mc/balance", routes![balance_teams])
+}
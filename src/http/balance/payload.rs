@@ -0,0 +1,32 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{database::models::{player::SimplePlayer, level::LevelGamemode}, socket::server::server_events::PartyData};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamBalanceRequest {
+    pub player_ids: Vec<String>,
+    pub parties: Vec<PartyData>,
+    #[serde(default)]
+    pub gamemode: Option<LevelGamemode>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamBalanceResponse {
+    pub parties: Vec<BalancedParty>,
+    // players left over once every party is full
+    pub unassigned: Vec<SimplePlayer>,
+    pub skill_difference: f64,
+    // false when there weren't enough players to bring every party up to its minimum
+    pub meets_minimums: bool
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalancedParty {
+    pub name: String,
+    pub players: Vec<SimplePlayer>,
+    pub average_skill: f64,
+    pub below_min: bool
+}
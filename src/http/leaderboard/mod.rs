@@ -2,7 +2,7 @@ use std::str::FromStr;
 
 use rocket::{Rocket, Build, State, serde::json::Json};
 
-use crate::{MarsAPIState, socket::leaderboard::{ScoreType, LeaderboardEntry, LeaderboardPeriod}, util::{r#macro::unwrap_helper, error::ApiErrorResponder}};
+use crate::{MarsAPIState, task::leaderboard_rebuild::run_leaderboard_rebuild, database::{Database, models::{leaderboard_snapshot::LeaderboardSnapshot, level::LevelGamemode}}, socket::leaderboard::{ratio_leaderboard::{RatioType, RatioLeaderboardEntry}, LeaderboardRebuildStatus, ScoreType, LeaderboardEntry, LeaderboardPeriod, LeaderboardPage, LeaderboardNeighborhood, LeaderboardScope}, util::{auth::AuthorizationToken, r#macro::unwrap_helper, error::ApiErrorResponder, time::get_u64_time_millis}};
 
 const PUBLIC_SCORE_TYPES : &[ScoreType] = &[
     ScoreType::Kills,
@@ -29,7 +29,16 @@ const PUBLIC_SCORE_TYPES : &[ScoreType] = &[
     ScoreType::HighestKillstreak
 ];
 
-#[get("/<score_type>/<period>?<limit>")]
+const DEFAULT_LEADERBOARD_LIMIT : u32 = 10;
+const MAX_LEADERBOARD_LIMIT : u32 = 50;
+const DEFAULT_NEIGHBORHOOD_RADIUS : u32 = 5;
+const MAX_NEIGHBORHOOD_RADIUS : u32 = 25;
+
+fn get_leaderboard_limit(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_LEADERBOARD_LIMIT).clamp(1, MAX_LEADERBOARD_LIMIT)
+}
+
+#[get("/<score_type>/<period>?<limit>", rank = 2)]
 async fn get_leaderboard_entries(
     state: &State<MarsAPIState>, 
     score_type: &str, 
@@ -41,11 +50,165 @@ async fn get_leaderboard_entries(
         return Err(ApiErrorResponder::unauthorized());
     };
     let period = unwrap_helper::return_default!(LeaderboardPeriod::from_str(period).ok(), Err(ApiErrorResponder::validation_error()));
-    let limit = limit.unwrap_or(10);
-    let leaderboard = score_type.to_leaderboard(&state.leaderboards).fetch_top(&period, if limit > 50 { 50 } else { limit }).await;
+    let leaderboard = score_type.to_leaderboard(&state.leaderboards).fetch_top(&period, get_leaderboard_limit(limit)).await;
+    Ok(Json(leaderboard))
+}
+
+#[get("/ratio/<ratio_type>?<limit>", rank = 1)]
+async fn get_ratio_leaderboard_entries(
+    state: &State<MarsAPIState>, 
+    ratio_type: &str, 
+    limit: Option<u32>
+) -> Result<Json<Vec<RatioLeaderboardEntry>>, ApiErrorResponder> {
+    let ratio_type = unwrap_helper::return_default!(RatioType::from_str(ratio_type).ok(), Err(ApiErrorResponder::validation_error()));
+    let leaderboard = ratio_type.to_leaderboard(&state.leaderboards.ratios).fetch_top(get_leaderboard_limit(limit)).await;
+    Ok(Json(leaderboard))
+}
+
+#[get("/rating/<gamemode>?<limit>", rank = 1)]
+async fn get_rating_leaderboard_entries(
+    state: &State<MarsAPIState>, 
+    gamemode: &str, 
+    limit: Option<u32>
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiErrorResponder> {
+    let gamemode = unwrap_helper::return_default!(LevelGamemode::from_str(gamemode).ok(), Err(ApiErrorResponder::validation_error()));
+    let leaderboard = state.leaderboards.ratings.fetch_top(&gamemode, get_leaderboard_limit(limit)).await;
+    Ok(Json(leaderboard))
+}
+
+#[get("/gamemode/<gamemode>/<score_type>/<period>?<limit>", rank = 1)]
+async fn get_gamemode_leaderboard_entries(
+    state: &State<MarsAPIState>, 
+    gamemode: &str,
+    score_type: &str, 
+    period: &str, 
+    limit: Option<u32>
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiErrorResponder> {
+    let gamemode = unwrap_helper::return_default!(LevelGamemode::from_str(gamemode).ok(), Err(ApiErrorResponder::validation_error()));
+    get_scoped_leaderboard_entries(state, LeaderboardScope::Gamemode(gamemode), score_type, period, limit).await
+}
+
+#[get("/map/<map_id>/<score_type>/<period>?<limit>", rank = 1)]
+async fn get_map_leaderboard_entries(
+    state: &State<MarsAPIState>, 
+    map_id: &str,
+    score_type: &str, 
+    period: &str, 
+    limit: Option<u32>
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiErrorResponder> {
+    get_scoped_leaderboard_entries(state, LeaderboardScope::Map(map_id.to_owned()), score_type, period, limit).await
+}
+
+async fn get_scoped_leaderboard_entries(
+    state: &State<MarsAPIState>, 
+    scope: LeaderboardScope,
+    score_type: &str, 
+    period: &str, 
+    limit: Option<u32>
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiErrorResponder> {
+    let score_type = unwrap_helper::return_default!(ScoreType::from_str(score_type).ok(), Err(ApiErrorResponder::validation_error()));
+    if !PUBLIC_SCORE_TYPES.contains(&score_type) {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let period = unwrap_helper::return_default!(LeaderboardPeriod::from_str(period).ok(), Err(ApiErrorResponder::validation_error()));
+    let leaderboard = score_type.to_leaderboard(&state.leaderboards).scoped(scope).fetch_top(&period, get_leaderboard_limit(limit)).await;
     Ok(Json(leaderboard))
 }
 
+#[get("/<score_type>/<period>/ranking?<offset>&<limit>", rank = 1)]
+async fn get_leaderboard_page(
+    state: &State<MarsAPIState>, 
+    score_type: &str, 
+    period: &str, 
+    offset: Option<u64>,
+    limit: Option<u32>
+) -> Result<Json<LeaderboardPage>, ApiErrorResponder> {
+    let score_type = unwrap_helper::return_default!(ScoreType::from_str(score_type).ok(), Err(ApiErrorResponder::validation_error()));
+    if !PUBLIC_SCORE_TYPES.contains(&score_type) {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let period = unwrap_helper::return_default!(LeaderboardPeriod::from_str(period).ok(), Err(ApiErrorResponder::validation_error()));
+    let page = score_type.to_leaderboard(&state.leaderboards).fetch_page(&period, offset.unwrap_or(0), get_leaderboard_limit(limit)).await;
+    Ok(Json(page))
+}
+
+#[get("/<score_type>/<period>/around/<player_id>?<radius>", rank = 2)]
+async fn get_leaderboard_neighborhood(
+    state: &State<MarsAPIState>, 
+    score_type: &str, 
+    period: &str, 
+    player_id: &str,
+    radius: Option<u32>
+) -> Result<Json<LeaderboardNeighborhood>, ApiErrorResponder> {
+    let score_type = unwrap_helper::return_default!(ScoreType::from_str(score_type).ok(), Err(ApiErrorResponder::validation_error()));
+    if !PUBLIC_SCORE_TYPES.contains(&score_type) {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let period = unwrap_helper::return_default!(LeaderboardPeriod::from_str(period).ok(), Err(ApiErrorResponder::validation_error()));
+    let player = unwrap_helper::return_default!(
+        state.player_cache.get(&state.database, &player_id.to_lowercase()).await, 
+        Err(ApiErrorResponder::missing_player())
+    );
+    let radius = radius.unwrap_or(DEFAULT_NEIGHBORHOOD_RADIUS).min(MAX_NEIGHBORHOOD_RADIUS);
+    let neighborhood = unwrap_helper::return_default!(
+        score_type.to_leaderboard(&state.leaderboards).fetch_around(&player.id_name(), &period, radius).await,
+        Err(ApiErrorResponder::missing_leaderboard_entry())
+    );
+    Ok(Json(neighborhood))
+}
+
+#[get("/<score_type>/<period>/<period_id>", rank = 2)]
+async fn get_leaderboard_snapshot(
+    state: &State<MarsAPIState>, 
+    score_type: &str, 
+    period: &str, 
+    period_id: &str
+) -> Result<Json<LeaderboardSnapshot>, ApiErrorResponder> {
+    let score_type = unwrap_helper::return_default!(ScoreType::from_str(score_type).ok(), Err(ApiErrorResponder::validation_error()));
+    if !PUBLIC_SCORE_TYPES.contains(&score_type) {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let period = unwrap_helper::return_default!(LeaderboardPeriod::from_str(period).ok(), Err(ApiErrorResponder::validation_error()));
+    if LeaderboardPeriod::from_period_id(period_id) != Some(period) {
+        return Err(ApiErrorResponder::validation_error());
+    };
+    let snapshot_id = LeaderboardSnapshot::get_snapshot_id(&score_type, period_id);
+    let snapshot = unwrap_helper::return_default!(
+        Database::find_by_id(&state.database.leaderboard_snapshots, &snapshot_id).await, 
+        Err(ApiErrorResponder::missing_leaderboard_snapshot())
+    );
+    Ok(Json(snapshot))
+}
+
+// only matches from the current year are read unless an earlier `since` is given, 0 rebuilds everything
+#[post("/rebuild?<since>")]
+async fn rebuild_leaderboards(
+    state: &State<MarsAPIState>, 
+    since: Option<u64>,
+    _auth_guard: AuthorizationToken
+) -> Result<Json<LeaderboardRebuildStatus>, ApiErrorResponder> {
+    let mut status = state.leaderboards.rebuild_status.lock().await;
+    if status.running {
+        return Err(ApiErrorResponder::leaderboard_rebuild_running());
+    };
+    *status = LeaderboardRebuildStatus { 
+        running: true, 
+        started_at: Some(get_u64_time_millis()), 
+        since: since.unwrap_or(state.leaderboards.periods.start_of_year_millis()), 
+        ..Default::default() 
+    };
+    tokio::spawn(run_leaderboard_rebuild(state.inner().clone()));
+    Ok(Json(status.clone()))
+}
+
+#[get("/rebuild")]
+async fn get_leaderboard_rebuild_status(
+    state: &State<MarsAPIState>, 
+    _auth_guard: AuthorizationToken
+) -> Json<LeaderboardRebuildStatus> {
+    Json(state.leaderboards.rebuild_status.lock().await.clone())
+}
+
 pub fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
-    rocket.mount("/mc/leaderboards", routes![get_leaderboard_entries])
+    rocket.mount("/mc/leaderboards", routes![get_leaderboard_entries, get_ratio_leaderboard_entries, get_rating_leaderboard_entries, get_gamemode_leaderboard_entries, get_map_leaderboard_entries, get_leaderboard_page, get_leaderboard_neighborhood, get_leaderboard_snapshot, rebuild_leaderboards, get_leaderboard_rebuild_status])
 }
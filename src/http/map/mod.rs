@@ -1,8 +1,11 @@
+use std::{str::FromStr, collections::{HashMap, HashSet}};
+
 use futures::future::join_all;
-use mongodb::bson::doc;
+use mongodb::{bson::{doc, Document}, options::FindOptions};
 use rocket::{Rocket, Build, State, serde::json::Json};
+use strum_macros::EnumString;
 
-use crate::{MarsAPIState, http::map::payload::MapLoadOneRequest, util::{auth::AuthorizationToken, time::get_u64_time_millis, r#macro::unwrap_helper, error::ApiErrorResponder}, database::{models::level::{Level, LevelRecords}, Database}};
+use crate::{MarsAPIState, http::map::payload::{MapLoadOneRequest, MapStatsResponse, MapListEntry, MapResponse, MapRateRequest, MapPage, MapListItem, MapSummary, MapListResponse}, util::{auth::AuthorizationToken, time::get_u64_time_millis, r#macro::unwrap_helper, error::ApiErrorResponder, responder::JsonResponder}, database::{models::{level::{Level, LevelRecords, LevelGamemode}, level_stats::LevelStats, level_record::{LevelRecordEntry, LevelRecordType}, level_rating::{LevelRating, LevelRatingAggregate, MIN_RATING_SCORE, MAX_RATING_SCORE, MAX_RATING_COMMENT_LENGTH}, r#match::MatchState, player::SimplePlayer}, Database}};
 
 mod payload;
 
@@ -58,17 +61,289 @@ async fn add_maps(
     Json(state.database.get_all_documents().await)
 }
 
-#[get("/")]
-async fn get_all_maps(state: &State<MarsAPIState>) -> Json<Vec<Level>> {
-    Json(state.database.get_all_documents::<Level>().await)
+// unknown or never-joined uuids are left unresolved
+async fn get_contributor_players(state: &MarsAPIState, maps: &Vec<&Level>) -> HashMap<String, SimplePlayer> {
+    let contributor_ids : Vec<String> = maps.iter().flat_map(|map| map.get_contributor_ids()).collect::<HashSet<String>>().into_iter().collect();
+    if contributor_ids.is_empty() {
+        return HashMap::new();
+    };
+    // the player cache is keyed by name, so uuids are resolved in one query instead
+    let players = Database::consume_cursor_into_owning_vec_option(state.database.players.clone_with_type::<Document>().find(
+        doc! { "_id": { "$in": contributor_ids } },
+        FindOptions::builder().projection(doc! { "name": 1 }).build()
+    ).await.ok()).await;
+    players.into_iter().filter_map(|player| {
+        let id = player.get_str("_id").ok()?.to_owned();
+        let name = player.get_str("name").ok()?.to_owned();
+        Some((id.clone(), SimplePlayer { name, id }))
+    }).collect()
+}
+
+#[derive(EnumString)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+enum MapSort {
+    Name,
+    Updated,
+    Plays,
+    Rating,
+    RatingCount
+}
+
+const DEFAULT_MAP_PAGE_SIZE : u64 = 50;
+const MAX_MAP_PAGE_SIZE : u64 = 500;
+
+async fn get_map_list_counts(state: &MarsAPIState, level_ids: &Vec<&String>) -> (HashMap<String, LevelRatingAggregate>, HashMap<String, u32>) {
+    let ratings = state.database.get_level_rating_aggregates(doc! { "levelId": { "$in": level_ids } }, "levelId").await;
+    let times_played : HashMap<String, u32> = Database::consume_cursor_into_owning_vec_option(state.database.level_stats.find(
+        doc! { "_id": { "$in": level_ids } },
+        FindOptions::builder().projection(doc! { "timesPlayed": 1 }).build()
+    ).await.ok()).await.into_iter().map(|level_stats| (level_stats.id, level_stats.times_played)).collect();
+    (ratings, times_played)
+}
+
+fn sort_map_list_entries(maps: &mut Vec<MapListEntry>, sort: &MapSort) {
+    match sort {
+        MapSort::Name => maps.sort_by(|a, b| a.map.name_lower.cmp(&b.map.name_lower)),
+        MapSort::Updated => maps.sort_by(|a, b| b.map.updated_at.cmp(&a.map.updated_at)),
+        MapSort::Plays => maps.sort_by(|a, b| b.times_played.cmp(&a.times_played)),
+        MapSort::Rating => maps.sort_by(|a, b| {
+            b.rating.average.total_cmp(&a.rating.average).then(b.rating.count.cmp(&a.rating.count))
+        }),
+        MapSort::RatingCount => maps.sort_by(|a, b| b.rating.count.cmp(&a.rating.count))
+    };
+}
+
+// without any filter or paging parameters the full list is returned as a plain array, like before
+#[get("/?<q>&<gamemode>&<author>&<sort>&<offset>&<limit>&<summary>")]
+async fn get_all_maps(
+    state: &State<MarsAPIState>, 
+    q: Option<&str>,
+    gamemode: Option<&str>,
+    author: Option<&str>,
+    sort: Option<&str>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    summary: Option<bool>
+) -> Result<Json<MapListResponse>, ApiErrorResponder> {
+    let paged = q.is_some() || gamemode.is_some() || author.is_some() || offset.is_some() || limit.is_some() || summary.is_some();
+    let sort = match sort {
+        Some(sort) => Some(unwrap_helper::return_default!(MapSort::from_str(sort).ok(), Err(ApiErrorResponder::validation_error()))),
+        None => None
+    };
+    if !paged {
+        let levels = state.database.get_all_documents::<Level>().await;
+        let (mut ratings, mut times_played) = get_map_list_counts(state, &levels.iter().map(|level| &level.id).collect()).await;
+        let mut maps : Vec<MapListEntry> = levels.into_iter().map(|map| {
+            let rating = ratings.remove(&map.id).unwrap_or_default();
+            let times_played = times_played.remove(&map.id).unwrap_or(0);
+            MapListEntry { map, rating, times_played }
+        }).collect();
+        if let Some(sort) = sort {
+            sort_map_list_entries(&mut maps, &sort);
+        };
+        let contributor_players = get_contributor_players(state, &maps.iter().map(|entry| &entry.map).collect()).await;
+        maps.iter_mut().for_each(|entry| entry.map.resolve_contributors(&contributor_players));
+        return Ok(Json(MapListResponse::List(maps)));
+    };
+
+    let sort = sort.unwrap_or(MapSort::Name);
+    let mut filter = doc! {};
+    if let Some(prefix) = q.map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty()) {
+        // range instead of a regex so the prefix never needs escaping
+        filter.insert("nameLower", doc! { "$gte": &prefix, "$lt": format!("{}\u{10FFFF}", prefix) });
+    };
+    if let Some(gamemode) = gamemode {
+        let gamemode = unwrap_helper::return_default!(LevelGamemode::from_str(gamemode).ok(), Err(ApiErrorResponder::validation_error()));
+        filter.insert("gamemodes", gamemode.to_string());
+    };
+    if let Some(author) = author {
+        let author = unwrap_helper::return_default!(state.player_cache.get(&state.database, author).await, Err(ApiErrorResponder::missing_player()));
+        filter.insert("authors.uuid", author.id);
+    };
+
+    let summary = summary.unwrap_or(false);
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_MAP_PAGE_SIZE).clamp(1, MAX_MAP_PAGE_SIZE);
+    let projection = if summary { Some(doc! { "records": 0, "goals": 0 }) } else { None };
+    let total = state.database.levels.count_documents(filter.clone(), None).await.unwrap_or(0);
+
+    let levels : Vec<Level> = match sort {
+        MapSort::Name | MapSort::Updated => {
+            let sort = if let MapSort::Name = sort { doc! { "nameLower": 1 } } else { doc! { "updatedAt": -1, "nameLower": 1 } };
+            let opts = FindOptions::builder().sort(sort).skip(offset).limit(limit as i64).projection(projection).build();
+            Database::consume_cursor_into_owning_vec_option(state.database.levels.find(filter, opts).await.ok()).await
+        },
+        _ => {
+            // plays and ratings live in other collections, so only ids are ranked before the page is loaded
+            let level_ids : Vec<String> = Database::consume_cursor_into_owning_vec_option(state.database.levels.clone_with_type::<Document>().find(
+                filter, 
+                FindOptions::builder().projection(doc! { "_id": 1 }).build()
+            ).await.ok()).await.into_iter().filter_map(|level| level.get_str("_id").ok().map(|id| id.to_owned())).collect();
+            let (ratings, times_played) = get_map_list_counts(state, &level_ids.iter().collect()).await;
+            let mut ranked : Vec<MapRank> = level_ids.into_iter().map(|id| {
+                let rating = ratings.get(&id).cloned().unwrap_or_default();
+                let times_played = times_played.get(&id).cloned().unwrap_or(0);
+                MapRank { id, rating, times_played }
+            }).collect();
+            match sort {
+                MapSort::Plays => ranked.sort_by(|a, b| b.times_played.cmp(&a.times_played)),
+                MapSort::Rating => ranked.sort_by(|a, b| {
+                    b.rating.average.total_cmp(&a.rating.average).then(b.rating.count.cmp(&a.rating.count))
+                }),
+                _ => ranked.sort_by(|a, b| b.rating.count.cmp(&a.rating.count))
+            };
+            let page_ids : Vec<String> = ranked.into_iter().skip(offset as usize).take(limit as usize).map(|rank| rank.id).collect();
+            let mut levels = Database::consume_cursor_into_owning_vec_option(state.database.levels.find(
+                doc! { "_id": { "$in": &page_ids } }, 
+                FindOptions::builder().projection(projection).build()
+            ).await.ok()).await;
+            levels.sort_by_key(|level| page_ids.iter().position(|id| id == &level.id));
+            levels
+        }
+    };
+
+    let (mut ratings, mut times_played) = get_map_list_counts(state, &levels.iter().map(|level| &level.id).collect()).await;
+    let mut maps : Vec<MapListEntry> = levels.into_iter().map(|map| {
+        let rating = ratings.remove(&map.id).unwrap_or_default();
+        let times_played = times_played.remove(&map.id).unwrap_or(0);
+        MapListEntry { map, rating, times_played }
+    }).collect();
+    let contributor_players = get_contributor_players(state, &maps.iter().map(|entry| &entry.map).collect()).await;
+    maps.iter_mut().for_each(|entry| entry.map.resolve_contributors(&contributor_players));
+
+    let next_offset = if offset + limit < total { Some(offset + limit) } else { None };
+    let maps = if summary {
+        maps.into_iter().map(|entry| MapListItem::Summary(MapSummary::from(entry))).collect()
+    } else {
+        maps.into_iter().map(MapListItem::Full).collect()
+    };
+    Ok(Json(MapListResponse::Page(MapPage { maps, total, next_offset })))
+}
+
+struct MapRank {
+    id: String,
+    rating: LevelRatingAggregate,
+    times_played: u32
 }
 
 #[get("/<map_id>")]
-async fn get_map_by_id(state: &State<MarsAPIState>, map_id: &str) -> Result<Json<Level>, ApiErrorResponder> {
+async fn get_map_by_id(state: &State<MarsAPIState>, map_id: &str) -> Result<Json<MapResponse>, ApiErrorResponder> {
+    let mut map : Level = unwrap_helper::return_default!(Database::find_by_id(&state.database.levels, map_id).await, Err(ApiErrorResponder::missing_map()));
+    map.resolve_contributors(&get_contributor_players(state, &vec![&map]).await);
+    let rating = state.database.get_level_rating_aggregates(doc! { "levelId": &map.id }, "levelId").await.remove(&map.id).unwrap_or_default();
+    let version_ratings = state.database.get_level_rating_aggregates(doc! { "levelId": &map.id }, "levelVersion").await;
+    Ok(Json(MapResponse { map, rating, version_ratings }))
+}
+
+// the player must have taken part in the (finished) match on this map
+#[post("/<map_id>/ratings", format = "json", data = "<rate_req>")]
+async fn rate_map(
+    state: &State<MarsAPIState>,
+    map_id: &str,
+    rate_req: Json<MapRateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<LevelRating>, ApiErrorResponder> {
+    let data = rate_req.0;
+    if data.score < MIN_RATING_SCORE || data.score > MAX_RATING_SCORE {
+        return Err(ApiErrorResponder::validation_error_with_message("Score must be between 1 and 5"));
+    };
+    let comment = data.comment.map(|comment| comment.trim().to_owned()).filter(|comment| !comment.is_empty());
+    if comment.as_ref().map_or(false, |comment| comment.chars().count() > MAX_RATING_COMMENT_LENGTH) {
+        return Err(ApiErrorResponder::validation_error_with_message("Comment is too long"));
+    };
+    let map = unwrap_helper::return_default!(Database::find_by_id(&state.database.levels, map_id).await, Err(ApiErrorResponder::missing_map()));
+    let rated_match = unwrap_helper::return_default!(state.match_cache.get(&state.database, &data.match_id).await, Err(ApiErrorResponder::missing_match()));
+    if rated_match.level.id != map.id || rated_match.get_state() != MatchState::Post {
+        return Err(ApiErrorResponder::validation_error_with_message("The match was not a finished match on this map"));
+    };
+    // the name is taken from the match, not from the request
+    let player = unwrap_helper::return_default!(
+        rated_match.participants.get(&data.player.id).map(|participant| participant.get_simple_player()),
+        Err(ApiErrorResponder::validation_error_with_message("The player did not participate in the match"))
+    );
+
+    let time_millis = get_u64_time_millis();
+    let id = LevelRating::get_id(&map.id, &rated_match.level.version, &player.id);
+    let created_at = Database::find_by_id(&state.database.level_ratings, &id).await.map_or(time_millis, |existing| existing.created_at);
+    let rating = LevelRating {
+        id,
+        level_id: map.id,
+        level_version: rated_match.level.version,
+        player,
+        match_id: rated_match.id,
+        score: data.score,
+        comment,
+        created_at,
+        updated_at: time_millis
+    };
+    state.database.save(&rating).await;
+    Ok(JsonResponder::created(rating))
+}
+
+#[get("/<map_id>/ratings?<version>&<limit>")]
+async fn get_map_ratings(
+    state: &State<MarsAPIState>,
+    map_id: &str,
+    version: Option<&str>,
+    limit: Option<i64>
+) -> Result<Json<Vec<LevelRating>>, ApiErrorResponder> {
+    let map = unwrap_helper::return_default!(Database::find_by_id(&state.database.levels, map_id).await, Err(ApiErrorResponder::missing_map()));
+    let mut filter = doc! { "levelId": &map.id };
+    if let Some(version) = version {
+        filter.insert("levelVersion", version);
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let opts = FindOptions::builder().sort(doc! { "updatedAt": -1 }).limit(limit).build();
+    Ok(Json(Database::consume_cursor_into_owning_vec_option(state.database.level_ratings.find(filter, opts).await.ok()).await))
+}
+
+#[get("/<map_id>/stats")]
+async fn get_map_stats(state: &State<MarsAPIState>, map_id: &str) -> Result<Json<MapStatsResponse>, ApiErrorResponder> {
+    let map = unwrap_helper::return_default!(Database::find_by_id(&state.database.levels, map_id).await, Err(ApiErrorResponder::missing_map()));
+    let stats = Database::find_by_id(&state.database.level_stats, &map.id).await.unwrap_or(LevelStats::empty(&map.id));
+
+    let times_played = stats.times_played.max(1) as f64;
+    let median_duration = {
+        let mut durations = stats.recent_durations.clone();
+        durations.sort();
+        durations.get(durations.len() / 2).cloned().unwrap_or(0)
+    };
+    let minutes_played = stats.total_duration as f64 / 60_000.0;
+    Ok(Json(MapStatsResponse {
+        map_id: map.id,
+        times_played: stats.times_played,
+        average_duration: (stats.total_duration as f64 / times_played) as u64,
+        median_duration,
+        party_win_rates: stats.party_results.iter().map(|(party_name, results)| {
+            (party_name.clone(), results.wins as f64 / results.matches.max(1) as f64)
+        }).collect(),
+        tie_rate: stats.ties as f64 / times_played,
+        average_participants: stats.total_participants as f64 / times_played,
+        kills_per_minute: if minutes_played > 0.0 { stats.total_kills as f64 / minutes_played } else { 0.0 },
+        gamemodes: stats.gamemode_plays,
+        last_played_at: stats.last_played_at
+    }))
+}
+
+#[get("/<map_id>/records/history?<record_type>&<limit>")]
+async fn get_map_record_history(
+    state: &State<MarsAPIState>,
+    map_id: &str,
+    record_type: Option<&str>,
+    limit: Option<i64>
+) -> Result<Json<Vec<LevelRecordEntry>>, ApiErrorResponder> {
     let map = unwrap_helper::return_default!(Database::find_by_id(&state.database.levels, map_id).await, Err(ApiErrorResponder::missing_map()));
-    Ok(Json(map))
+    let mut filter = doc! { "levelId": &map.id };
+    if let Some(record_type) = record_type {
+        let record_type = unwrap_helper::return_default!(LevelRecordType::from_str(record_type).ok(), Err(ApiErrorResponder::validation_error()));
+        filter.insert("recordType", record_type.to_string());
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let opts = FindOptions::builder().sort(doc! { "achievedAt": -1 }).limit(limit).build();
+    let history = Database::consume_cursor_into_owning_vec_option(state.database.level_records.find(filter, opts).await.ok()).await;
+    Ok(Json(history))
 }
 
 pub fn mount(build: Rocket<Build>) -> Rocket<Build> {
-    build.mount("/mc/maps", routes![add_maps, get_all_maps, get_map_by_id])
+    build.mount("/mc/maps", routes![add_maps, get_all_maps, get_map_by_id, get_map_stats, get_map_record_history, rate_map, get_map_ratings])
 }
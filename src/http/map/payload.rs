@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use serde::{Serialize, Deserialize};
 
-use crate::database::models::level::{LevelGamemode, LevelContributor};
+use crate::database::models::{level::{Level, LevelGamemode, LevelContributor}, level_rating::LevelRatingAggregate, player::SimplePlayer};
 
 #[derive(Serialize, Deserialize)]
 pub struct MapLoadOneRequest {
@@ -12,3 +14,99 @@ pub struct MapLoadOneRequest {
     pub authors: Vec<LevelContributor>,
     pub contributors: Vec<LevelContributor>
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapStatsResponse {
+    pub map_id: String,
+    pub times_played: u32,
+    pub average_duration: u64,
+    // over the most recent matches only
+    pub median_duration: u64,
+    pub party_win_rates: HashMap<String, f64>,
+    pub tie_rate: f64,
+    pub average_participants: f64,
+    pub kills_per_minute: f64,
+    pub gamemodes: HashMap<LevelGamemode, u32>,
+    pub last_played_at: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapListEntry {
+    #[serde(flatten)]
+    pub map: Level,
+    pub rating: LevelRatingAggregate,
+    pub times_played: u32
+}
+
+// leaves out records and goals
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapSummary {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub gamemodes: Vec<LevelGamemode>,
+    pub authors: Vec<LevelContributor>,
+    pub updated_at: u64,
+    pub rating: LevelRatingAggregate,
+    pub times_played: u32
+}
+
+impl From<MapListEntry> for MapSummary {
+    fn from(entry: MapListEntry) -> Self {
+        MapSummary {
+            id: entry.map.id,
+            name: entry.map.name,
+            version: entry.map.version,
+            gamemodes: entry.map.gamemodes,
+            authors: entry.map.authors,
+            updated_at: entry.map.updated_at,
+            rating: entry.rating,
+            times_played: entry.times_played
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MapListItem {
+    Full(MapListEntry),
+    Summary(MapSummary)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapPage {
+    pub maps: Vec<MapListItem>,
+    pub total: u64,
+    pub next_offset: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MapListResponse {
+    List(Vec<MapListEntry>),
+    Page(MapPage)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapResponse {
+    #[serde(flatten)]
+    pub map: Level,
+    pub rating: LevelRatingAggregate,
+    pub version_ratings: HashMap<String, LevelRatingAggregate>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapRateRequest {
+    pub player: SimplePlayer,
+    pub match_id: String,
+    pub score: u8,
+    #[serde(default)]
+    pub comment: Option<String>
+}
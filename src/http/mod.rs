@@ -11,3 +11,7 @@ pub mod leaderboard;
 pub mod tag;
 pub mod perks;
 pub mod r#match;
+pub mod xp_multiplier;
+pub mod balance;
+pub mod rotation;
+pub mod online;
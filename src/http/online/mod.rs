@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use rocket::{Rocket, Build, State, serde::json::Json};
+use serde::{Serialize, Deserialize};
+
+use crate::{MarsAPIState, database::models::presence::PlayerPresence};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnlinePlayersResponse {
+    pub total: usize,
+    pub servers: HashMap<String, Vec<PlayerPresence>>
+}
+
+#[get("/")]
+async fn get_online_players(state: &State<MarsAPIState>) -> Json<OnlinePlayersResponse> {
+    let mut servers : HashMap<String, Vec<PlayerPresence>> = HashMap::new();
+    for server_id in PlayerPresence::get_online_servers(state).await.into_iter() {
+        let mut players = PlayerPresence::get_server_players(state, &server_id).await;
+        players.sort_by(|a, b| a.player.name.to_lowercase().cmp(&b.player.name.to_lowercase()));
+        servers.insert(server_id, players);
+    };
+    let total = servers.values().map(|players| players.len()).sum();
+    Json(OnlinePlayersResponse { total, servers })
+}
+
+pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
+    rocket_build.mount("/mc/online", routes![get_online_players])
+}
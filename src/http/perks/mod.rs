@@ -1,6 +1,6 @@
 use rocket::{Rocket, State, Build, serde::json::Json};
 
-use crate::{MarsAPIState, database::models::{join_sound::JoinSound, player::Player}, util::{auth::AuthorizationToken, responder::JsonResponder, error::ApiErrorResponder}};
+use crate::{MarsAPIState, database::models::{join_sound::JoinSound, player::Player, rank::Rank}, util::{auth::AuthorizationToken, responder::JsonResponder, error::ApiErrorResponder, r#macro::unwrap_helper}};
 
 use self::payload::JoinSoundSetRequest;
 
@@ -13,6 +13,7 @@ fn get_join_sounds(
     Json(&state.config.data.join_sounds)
 }
 
+// a sound is unlocked by a level reward or by a rank carrying the sound's permission
 #[post("/join_sounds/<player_id>/sound", format = "json", data = "<set_join_req>")]
 async fn update_join_sound(
     state: &State<MarsAPIState>,
@@ -20,20 +21,28 @@ async fn update_join_sound(
     set_join_req: Json<JoinSoundSetRequest>,
     _auth_guard: AuthorizationToken
 ) -> Result<JsonResponder<Player>, ApiErrorResponder> {
-    match state.player_cache.get(&state.database, player_id).await {
-        Some(mut p) => {
-            let current_sound = set_join_req.0.active_join_sound_id;
-            if p.active_join_sound_id == current_sound {
-                return Ok(JsonResponder::ok(p));
+    let mut player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
+    let current_sound = set_join_req.0.active_join_sound_id;
+    if player.active_join_sound_id == current_sound {
+        return Ok(JsonResponder::ok(player));
+    };
+    if let Some(sound_id) = &current_sound {
+        let sound = unwrap_helper::return_default!(
+            state.config.data.join_sounds.iter().find(|sound| &sound.id == sound_id), 
+            Err(ApiErrorResponder::join_sound_missing())
+        );
+        if !player.join_sound_ids.contains(&sound.id) {
+            let ranks = state.database.get_all_documents::<Rank>().await;
+            let has_permission = Rank::resolve_effective(&ranks, &player.rank_ids).iter()
+                .any(|rank| rank.permissions.iter().any(|permission| permission == &sound.permission || permission == "*"));
+            if !has_permission {
+                return Err(ApiErrorResponder::join_sound_locked());
             };
-            p.active_join_sound_id = current_sound;
-            state.player_cache.set(&state.database, &p.name, &p, true).await;
-            Ok(JsonResponder::ok(p))
-        },
-        None => {
-            Err(ApiErrorResponder::missing_player())
-        }
-    }
+        };
+    };
+    player.active_join_sound_id = current_sound;
+    state.player_cache.set(&state.database, &player.name, &player, true).await;
+    Ok(JsonResponder::ok(player))
 }
 
 pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
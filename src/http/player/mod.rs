@@ -1,15 +1,16 @@
 mod payloads;
 
 use futures::future::join_all;
-use mongodb::bson::doc;
+use mongodb::{bson::doc, options::FindOptions};
 use payloads::PlayerPreLoginRequest;
-use rocket::{serde::json::Json, Build, Rocket, State, http::Status};
+use rocket::{serde::json::{self, Json}, Build, Rocket, State, http::Status};
 use uuid::Uuid;
-use crate::{util::{auth::AuthorizationToken, error::{ApiError, ApiErrorResponder}, string::to_utf8_byte_array, responder::{JsonResponder, EmptyResponse}, time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState, database::{Database, models::{punishment::{Punishment, PunishmentKind, StaffNote}, player::{Player, PlayerStats, SessionRecord}, session::Session, rank::Rank, tag::Tag}}, http::player::payloads::{PlayerLoginRequest, PlayerLookupResponse, PlayerAddNoteRequest, PlayerSetActiveTagRequest}, socket::leaderboard::{Leaderboard, ScoreType, LeaderboardPeriod}};
+use crate::{util::{auth::AuthorizationToken, error::{ApiError, ApiErrorResponder}, string::to_utf8_byte_array, responder::{JsonResponder, EmptyResponse}, time::get_u64_time_millis, r#macro::unwrap_helper}, MarsAPIState, database::{Database, models::{punishment::{Punishment, PunishmentKind, StaffNote}, player::{Player, PlayerStats, SessionRecord, PlayerNameHistoryEntry, PlayerSearchDocument}, session::{Session, ConcurrentSessionPolicy, CONCURRENT_SESSION_MAX_CREDIT_MS}, presence::PlayerPresence, rank::{Rank, RankGrant}, tag::Tag, rating::RatingChange, level::{Level, LevelGamemode}}}, http::player::payloads::{PlayerLoginRequest, PlayerLookupResponse, PlayerAddNoteRequest, PlayerSetActiveTagRequest, PlayerRankGrantRequest, PlayerProfile, PlayerPermissionsResponse, RecordsHeld, PlayerMapEntry, PlayerMapsResponse, PlayerSearchResult, PlayerSessionPage, PlayerSessionAnalytics, PlayerPresenceResponse}, socket::{leaderboard::{Leaderboard, ScoreType, LeaderboardPeriod}, event_type::EventType, player::player_events::{DisconnectPlayerData, MessageData}}};
 use sha2::{Sha256, Digest};
+use chrono_tz::Tz;
 
 use self::payloads::{PlayerPreLoginResponse, PlayerPreLoginResponder, PlayerLoginResponse, PlayerLogoutRequest, PlayerProfileResponder, PlayerProfileResponse, PlayerAltResponse};
-use std::{time::{SystemTime, UNIX_EPOCH}, collections::HashMap};
+use std::{time::{SystemTime, UNIX_EPOCH}, collections::HashMap, str::FromStr};
 
 use super::punishment::payloads::PunishmentIssueRequest;
 
@@ -30,8 +31,7 @@ pub async fn prelogin(
     let player_optional = Database::find_by_id(&state.database.players, &data.player.id).await;
     if let Some(mut returning_player) = player_optional {
         println!("the player was found!");
-        returning_player.name = data.player.name.clone();
-        returning_player.name_lower = returning_player.name.to_lowercase();
+        returning_player.observe_name(&data.player.name, get_u64_time_millis());
         if !returning_player.ips.contains(&ip) {
             returning_player.ips.push(ip.clone());
         };
@@ -74,13 +74,25 @@ pub async fn prelogin(
             first_joined_at: time_millis,
             last_joined_at: time_millis,
             rank_ids: Vec::new(),
+            rank_grants: Vec::new(),
             tag_ids: Vec::new(),
             active_tag_id: None,
             stats: PlayerStats::default(),
             gamemode_stats: HashMap::new(),
             notes: Vec::new(),
             last_session_id: None,
-            active_join_sound_id: None
+            active_join_sound_id: None,
+            join_sound_ids: Vec::new(),
+            level_ups: Vec::new(),
+            promoted_rank_ids: Vec::new(),
+            pending_messages: Vec::new(),
+            ratings: HashMap::new(),
+            name_history: vec![PlayerNameHistoryEntry {
+                name: data.player.name.clone(),
+                name_lower: data.player.name.to_lowercase(),
+                first_seen_at: time_millis as u64,
+                last_seen_at: time_millis as u64
+            }]
         };
 
         state.player_cache.set(&state.database, &player.name, &player, true).await;
@@ -111,6 +123,15 @@ macro_rules! async_extract_player_from_url_v2 {
     }
 }
 
+// same as above but falls back to names the player used before
+macro_rules! async_extract_player_from_url_or_past_name {
+    ( $e:expr, $s:expr ) => {
+        if let Some(player) = ($s).player_cache.get(&($s).database, ($e)).await { player } 
+        else if let Some(player) = ($s).database.find_player_by_past_name(($e)).await { player }
+        else { return Err(ApiErrorResponder::missing_player()) }
+    }
+}
+
 
 #[post("/<player_id>/login", format = "json", data = "<login_req>")]
 pub async fn login(
@@ -131,6 +152,33 @@ pub async fn login(
     };
 
     let time_millis : u64 = u64::try_from(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()).unwrap_or(u64::MAX);
+    if let Some(mut existing_session) = state.database.get_active_player_session(&player).await {
+        warn!(
+            "Concurrent session for {}: session '{}' on '{}' has been open since {}, now logging in on '{}'", 
+            player.id_name(), existing_session.id, existing_session.server_id, existing_session.created_at, auth_guard.server_id
+        );
+        if state.config.options.concurrent_session_policy == ConcurrentSessionPolicy::Reject {
+            return Err(ApiErrorResponder::session_already_active());
+        };
+        // ended when its server was last heard from, the old server's logout is then refused as inactive
+        let last_alive_time = state.redis.get_unchecked::<u64>(&format!("server:{}:last_alive_time", existing_session.server_id)).await;
+        existing_session.ended_at = Some(last_alive_time.unwrap_or(existing_session.created_at).clamp(existing_session.created_at, time_millis));
+        let credited_playtime = existing_session.length().unwrap_or(0).min(CONCURRENT_SESSION_MAX_CREDIT_MS);
+        player.stats.server_playtime += credited_playtime;
+        state.leaderboards.server_playtime.increment(&player.id_name(), Some(u32::try_from(credited_playtime).unwrap_or(u32::MAX))).await;
+        state.database.save(&existing_session).await;
+        PlayerPresence::set_offline(state, &player.id, &existing_session.id).await;
+        if existing_session.server_id != auth_guard.server_id {
+            let notified = state.server_outbox.send(&existing_session.server_id, EventType::DisconnectPlayer, DisconnectPlayerData {
+                player_id: player.id.clone(),
+                reason: String::from("You logged in from another server")
+            }).await;
+            if !notified {
+                warn!("Could not notify '{}' to disconnect {}, server is not connected", existing_session.server_id, player.id_name());
+            };
+        };
+    };
+
     let ip = hash_ip(&state, &data.ip);
     let active_session = Session {
         id: Uuid::new_v4().to_string(),
@@ -142,6 +190,12 @@ pub async fn login(
     };
 
     state.database.save(&active_session).await;
+    PlayerPresence {
+        player: player.to_simple(),
+        server_id: active_session.server_id.clone(),
+        session_id: active_session.id.clone(),
+        joined_at: time_millis
+    }.set_online(state).await;
     let mut player_ranks = player.rank_ids.clone();
     let mut default_ranks : Vec<String> = Rank::find_default(&state.database).await
         .iter()
@@ -150,9 +204,26 @@ pub async fn login(
     player_ranks.append(&mut default_ranks);
     player_ranks.dedup();
 
+    for expired_grant in player.remove_expired_rank_grants() {
+        info!("Rank grant '{}' expired for {}", expired_grant.rank_id, player.id_name());
+    };
     player.last_joined_at = time_millis as f64;
     player.last_session_id = Some(active_session.id.clone());
 
+    // kept for the next login if this server isn't connected to us
+    let mut undelivered_messages : Vec<String> = Vec::new();
+    for message in player.pending_messages.drain(..) {
+        let delivered = state.server_outbox.send(&active_session.server_id, EventType::Message, MessageData {
+            message: message.clone(),
+            sound: None,
+            player_ids: vec![player.id.clone()]
+        }).await;
+        if !delivered {
+            undelivered_messages.push(message);
+        };
+    };
+    player.pending_messages = undelivered_messages;
+
     state.player_cache.set(&state.database, &player.name, &player, true).await;
 
     Ok(JsonResponder::from(PlayerLoginResponse { active_session }, Status::Created))
@@ -191,7 +262,13 @@ pub async fn logout(
         player.stats.records.longest_session = Some(SessionRecord { session_id: session.id.clone(), length: data.playtime.clone() });
     };
 
+    // the player already left the server, so promotions reached here are announced on their next login
+    for (rank, promotion) in player.apply_rank_promotions(&state).await {
+        player.pending_messages.push(promotion.get_message(&rank));
+    };
+
     state.database.save(&session).await;
+    PlayerPresence::set_offline(state, &player.id, &session.id).await;
     state.player_cache.set(&state.database, &player.name, &player, true).await;
 
     Ok(JsonResponder::ok(EmptyResponse {}))
@@ -205,8 +282,12 @@ pub async fn profile(
     include_leaderboard_positions: bool
 ) -> Result<PlayerProfileResponder, ApiErrorResponder> {
     let player_id = player_id.to_lowercase();
-    let player : Player = async_extract_player_from_url_v2!(&player_id, state);
-    let profile = player.sanitized_copy();
+    let player : Player = async_extract_player_from_url_or_past_name!(&player_id, state);
+    let records_held = RecordsHeld {
+        current: state.database.level_records.count_documents(doc! { "holder.id": &player.id, "brokenAt": null }, None).await.unwrap_or(0),
+        total: state.database.level_records.count_documents(doc! { "holder.id": &player.id }, None).await.unwrap_or(0)
+    };
+    let profile = PlayerProfile::from_player(player.sanitized_copy(), records_held);
     if !include_leaderboard_positions {
         return Ok(PlayerProfileResponder::RawProfile(profile))
     };
@@ -245,7 +326,7 @@ pub async fn profile(
             (lb.score_type.clone(), lb.get_position(&player_id, &LeaderboardPeriod::AllTime).await)
         };
 
-        lb_position_tasks.push(wrapper(player.id_name()));
+        lb_position_tasks.push(wrapper(profile.player.id_name()));
     }
 
     join_all(lb_position_tasks).await.into_iter().filter(|pos_opt| pos_opt.1.is_some()).for_each(|pos| {
@@ -329,7 +410,7 @@ pub async fn lookup_player(
     include_alts: bool,
     _auth_guard: AuthorizationToken
 ) -> Result<JsonResponder<PlayerLookupResponse>, ApiErrorResponder> {
-    let player : Player = async_extract_player_from_url_v2!(&player_id, state);
+    let player : Player = async_extract_player_from_url_or_past_name!(&player_id, state);
     let alts : Vec<PlayerAltResponse> = {
         let mut alts : Vec<PlayerAltResponse> = Vec::new();
         if include_alts {
@@ -470,18 +551,50 @@ async fn delete_player_tag(
 
 }
 
-#[put("/<player_id>/ranks/<rank_id>")]
+#[put("/<player_id>/ranks/<rank_id>", format = "json", data = "<grant_req>")]
 async fn add_player_rank(
     state: &State<MarsAPIState>, 
     player_id: &str, 
     rank_id: &str, 
+    grant_req: Result<Json<PlayerRankGrantRequest>, json::Error<'_>>,
     _auth_guard: AuthorizationToken
 ) -> Result<Json<Player>, ApiErrorResponder> {
+    // an empty body grants with the defaults, anything that doesn't parse is refused rather than made permanent
+    let grant_req = match grant_req {
+        Ok(grant_req) => Some(grant_req),
+        Err(json::Error::Parse(raw, _)) if raw.trim().is_empty() => None,
+        Err(_) => return Err(ApiErrorResponder::validation_error())
+    };
     let mut player = unwrap_helper::return_default!(state.player_cache.get(&state.database, player_id).await, Err(ApiErrorResponder::missing_player()));
     let rank = unwrap_helper::return_default!(state.database.find_by_id_or_name::<Rank>(rank_id).await, Err(ApiErrorResponder::missing_rank()));
 
-    if player.rank_ids.contains(&rank.id) { return Err(ApiErrorResponder::rank_already_present()); };
-    player.rank_ids.push(rank.id);
+    // timed grants may be renewed, permanent ranks may not be granted twice
+    let timed_grant = player.rank_grants.iter().find(|grant| grant.rank_id == rank.id && grant.expires_at.is_some()).cloned();
+    if player.rank_ids.contains(&rank.id) && timed_grant.is_none() { return Err(ApiErrorResponder::rank_already_present()); };
+
+    let time_millis = get_u64_time_millis();
+    let grant = match grant_req {
+        Some(grant_req) => {
+            let grant_req = grant_req.0;
+            let expires_at = match (grant_req.duration, &timed_grant) {
+                (Some(duration), _) => Some(time_millis.saturating_add(duration)),
+                (None, Some(timed_grant)) if !grant_req.permanent => timed_grant.expires_at,
+                (None, _) => None
+            };
+            RankGrant { 
+                rank_id: rank.id, 
+                granted_at: time_millis, 
+                expires_at, 
+                reason: grant_req.reason.or(timed_grant.as_ref().and_then(|grant| grant.reason.clone())), 
+                granter: grant_req.granter.or(timed_grant.as_ref().and_then(|grant| grant.granter.clone())) 
+            }
+        },
+        None => match timed_grant {
+            Some(timed_grant) => timed_grant,
+            None => RankGrant { rank_id: rank.id, granted_at: time_millis, expires_at: None, reason: None, granter: None }
+        }
+    };
+    player.set_rank_grant(grant);
 
     state.player_cache.set(&state.database, &player.name, &player, true).await;
     Ok(Json(player))
@@ -498,12 +611,241 @@ async fn delete_player_rank(
     let rank = unwrap_helper::return_default!(state.database.find_by_id_or_name::<Rank>(rank_id).await, Err(ApiErrorResponder::missing_rank()));
 
     if !player.rank_ids.contains(&rank.id) { return Err(ApiErrorResponder::rank_not_present()); };
-    player.rank_ids.retain(|rank_id| { rank_id != rank.id.as_str() });
+    player.remove_rank(&rank.id);
 
     state.player_cache.set(&state.database, &player.name, &player, true).await;
     Ok(Json(player))
 }
 
+#[get("/<player_id>/permissions")]
+async fn get_player_permissions(
+    state: &State<MarsAPIState>, 
+    player_id: &str, 
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<PlayerPermissionsResponse>, ApiErrorResponder> {
+    let player = async_extract_player_from_url_v2!(player_id, state);
+    let ranks = state.database.get_all_documents::<Rank>().await;
+
+    let effective_ranks = Rank::resolve_effective(&ranks, &player.rank_ids);
+
+    let mut permissions : Vec<String> = effective_ranks.iter().flat_map(|rank| rank.permissions.iter().cloned()).collect();
+    permissions.sort();
+    permissions.dedup();
+    let prefix = effective_ranks.iter()
+        .filter(|rank| rank.prefix.is_some())
+        .max_by_key(|rank| rank.priority)
+        .and_then(|rank| rank.prefix.clone());
+
+    Ok(JsonResponder::ok(PlayerPermissionsResponse { 
+        rank_ids: effective_ranks.iter().map(|rank| rank.id.clone()).collect(), 
+        permissions, 
+        prefix 
+    }))
+}
+
+#[get("/<player_id>/ratings/history?<gamemode>&<limit>")]
+async fn get_player_rating_history(
+    state: &State<MarsAPIState>, 
+    player_id: &str, 
+    gamemode: Option<&str>,
+    limit: Option<i64>
+) -> Result<Json<Vec<RatingChange>>, ApiErrorResponder> {
+    let player = async_extract_player_from_url_v2!(&player_id.to_lowercase(), state);
+    let mut filter = doc! { "player.id": &player.id };
+    if let Some(gamemode) = gamemode {
+        let gamemode = unwrap_helper::return_default!(LevelGamemode::from_str(gamemode).ok(), Err(ApiErrorResponder::validation_error()));
+        filter.insert("gamemode", gamemode.to_string());
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).limit(limit).build();
+    let history = Database::consume_cursor_into_owning_vec_option(state.database.rating_changes.find(filter, opts).await.ok()).await;
+    Ok(Json(history))
+}
+
+// maps the player is credited on, most played first
+#[get("/<player_id>/maps")]
+async fn get_player_maps(
+    state: &State<MarsAPIState>, 
+    player_id: &str
+) -> Result<Json<PlayerMapsResponse>, ApiErrorResponder> {
+    let player = async_extract_player_from_url_v2!(&player_id.to_lowercase(), state);
+    let levels : Vec<Level> = Database::consume_cursor_into_owning_vec_option(state.database.levels.find(doc! {
+        "$or": [{ "authors.uuid": &player.id }, { "contributors.uuid": &player.id }]
+    }, None).await.ok()).await;
+    let level_ids : Vec<&String> = levels.iter().map(|level| &level.id).collect();
+    let mut stats : HashMap<String, _> = Database::consume_cursor_into_owning_vec_option(state.database.level_stats.find(doc! {
+        "_id": { "$in": &level_ids }
+    }, None).await.ok()).await.into_iter().map(|level_stats| (level_stats.id.clone(), level_stats)).collect();
+    let mut ratings = state.database.get_level_rating_aggregates(doc! { "levelId": { "$in": &level_ids } }, "levelId").await;
+
+    let mut maps : Vec<PlayerMapEntry> = levels.into_iter().map(|level| {
+        let author = level.authors.iter().find(|contributor| contributor.uuid == player.id);
+        let credit = author.or(level.contributors.iter().find(|contributor| contributor.uuid == player.id));
+        let level_stats = stats.remove(&level.id);
+        PlayerMapEntry {
+            map_id: level.id.clone(),
+            author: author.is_some(),
+            contribution: credit.and_then(|contributor| contributor.contribution.clone()),
+            times_played: level_stats.as_ref().map_or(0, |level_stats| level_stats.times_played),
+            last_played_at: level_stats.and_then(|level_stats| level_stats.last_played_at),
+            rating: ratings.remove(&level.id).unwrap_or_default(),
+            name: level.name,
+            version: level.version,
+            gamemodes: level.gamemodes
+        }
+    }).collect();
+    maps.sort_by(|a, b| b.times_played.cmp(&a.times_played));
+    let total_times_played = maps.iter().map(|map| map.times_played as u64).sum();
+    Ok(Json(PlayerMapsResponse { maps, total_times_played }))
+}
+
+const DEFAULT_SEARCH_RESULTS : usize = 10;
+const MAX_SEARCH_RESULTS : usize = 25;
+// candidates fetched per query before ranking
+const SEARCH_CANDIDATES : i64 = 100;
+const FUZZY_SEARCH_CANDIDATES : i64 = 500;
+// typo tolerant matching only looks at names sharing this many leading characters with the query
+const FUZZY_SEARCH_ANCHOR : usize = 2;
+
+// fewest edits turning `query` into some prefix of `name`
+fn prefix_edit_distance(query: &str, name: &str) -> usize {
+    let query : Vec<char> = query.chars().collect();
+    let name : Vec<char> = name.chars().collect();
+    let mut previous : Vec<usize> = (0..=name.len()).collect();
+    for (i, query_char) in query.iter().enumerate() {
+        let mut current : Vec<usize> = vec![i + 1];
+        for (j, name_char) in name.iter().enumerate() {
+            let substitution = previous[j] + if query_char == name_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        };
+        previous = current;
+    };
+    previous.into_iter().min().unwrap_or(0)
+}
+
+fn get_allowed_typos(query: &str) -> usize {
+    match query.len() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2
+    }
+}
+
+// lower is better: exact, current prefix, past prefix, current with typos, past with typos, then by edit distance
+fn rank_search_match(candidate: &PlayerSearchDocument, query: &str) -> Option<(u8, usize, Option<String>)> {
+    if candidate.name_lower == query {
+        return Some((0, 0, None));
+    };
+    if candidate.name_lower.starts_with(query) {
+        return Some((1, 0, None));
+    };
+    let past_names : Vec<&PlayerNameHistoryEntry> = candidate.name_history.iter().filter(|entry| entry.name_lower != candidate.name_lower).collect();
+    if let Some(entry) = past_names.iter().find(|entry| entry.name_lower.starts_with(query)) {
+        return Some((2, 0, Some(entry.name.clone())));
+    };
+    let allowed_typos = get_allowed_typos(query);
+    let distance = prefix_edit_distance(query, &candidate.name_lower);
+    if distance <= allowed_typos {
+        return Some((3, distance, None));
+    };
+    past_names.iter()
+        .map(|entry| (prefix_edit_distance(query, &entry.name_lower), entry))
+        .filter(|(distance, _)| *distance <= allowed_typos)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(distance, entry)| (4, distance, Some(entry.name.clone())))
+}
+
+#[get("/search?<q>&<limit>")]
+async fn search_players(
+    state: &State<MarsAPIState>, 
+    q: &str,
+    limit: Option<usize>
+) -> Result<Json<Vec<PlayerSearchResult>>, ApiErrorResponder> {
+    let query = q.trim().to_lowercase();
+    // minecraft names only, which also keeps the query safe to use as a pattern
+    if query.is_empty() || query.len() > 16 || !query.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(ApiErrorResponder::validation_error_with_message("Search must be part of a Minecraft name"));
+    };
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_RESULTS).clamp(1, MAX_SEARCH_RESULTS);
+
+    // every pattern is anchored so the name indexes can be used
+    let mut candidates = state.database.search_player_names(&format!("^{}", query), SEARCH_CANDIDATES).await;
+    if candidates.len() < limit && get_allowed_typos(&query) > 0 {
+        let anchor = &query[..FUZZY_SEARCH_ANCHOR];
+        for candidate in state.database.search_player_names(&format!("^{}", anchor), FUZZY_SEARCH_CANDIDATES).await.into_iter() {
+            if !candidates.iter().any(|existing| existing.id == candidate.id) {
+                candidates.push(candidate);
+            };
+        };
+    };
+    let mut ranked : Vec<(u8, usize, Option<String>, PlayerSearchDocument)> = candidates.into_iter().filter_map(|candidate| {
+        let (tier, distance, matched_name) = rank_search_match(&candidate, &query)?;
+        Some((tier, distance, matched_name, candidate))
+    }).collect();
+    ranked.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(b.3.last_joined_at.total_cmp(&a.3.last_joined_at)));
+    Ok(Json(ranked.into_iter().take(limit).map(|(_, _, matched_name, candidate)| PlayerSearchResult {
+        id: candidate.id,
+        name: candidate.name,
+        matched_name,
+        last_joined_at: candidate.last_joined_at
+    }).collect()))
+}
+
+// ips are only included for authorized callers
+#[get("/<player_id>/sessions?<offset>&<limit>")]
+async fn get_player_sessions(
+    state: &State<MarsAPIState>, 
+    player_id: &str,
+    offset: Option<u64>,
+    limit: Option<i64>,
+    auth_guard: Option<AuthorizationToken>
+) -> Result<Json<PlayerSessionPage>, ApiErrorResponder> {
+    let player = async_extract_player_from_url_v2!(&player_id.to_lowercase(), state);
+    let filter = doc! { "player.id": &player.id };
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let total = state.database.sessions.count_documents(filter.clone(), None).await.unwrap_or(0);
+    let opts = FindOptions::builder().sort(doc! { "createdAt": -1 }).skip(offset).limit(limit).build();
+    let mut sessions = Database::consume_cursor_into_owning_vec_option(state.database.sessions.find(filter, opts).await.ok()).await;
+    if auth_guard.is_none() {
+        sessions.iter_mut().for_each(|session| session.ip = String::new());
+    };
+    let next_offset = if offset + (limit as u64) < total { Some(offset + limit as u64) } else { None };
+    Ok(Json(PlayerSessionPage { sessions, total, next_offset }))
+}
+
+#[get("/<player_id>/sessions/analytics?<days>&<timezone>")]
+async fn get_player_session_analytics(
+    state: &State<MarsAPIState>, 
+    player_id: &str,
+    days: Option<u64>,
+    timezone: Option<&str>
+) -> Result<Json<PlayerSessionAnalytics>, ApiErrorResponder> {
+    let player = async_extract_player_from_url_v2!(&player_id.to_lowercase(), state);
+    let timezone = match timezone {
+        Some(timezone) => unwrap_helper::return_default!(Tz::from_str(timezone).ok(), Err(ApiErrorResponder::validation_error_with_message("Unknown timezone"))),
+        None => state.leaderboards.periods.timezone
+    };
+    let now = get_u64_time_millis();
+    let since = now.saturating_sub(days.unwrap_or(30).clamp(1, 365) * 86_400_000);
+    let sessions = Database::consume_cursor_into_owning_vec_option(state.database.sessions.find(doc! {
+        "player.id": &player.id,
+        "$or": [{ "endedAt": null }, { "endedAt": { "$gte": since as i64 } }]
+    }, None).await.ok()).await;
+    let current_session_id = PlayerPresence::get(state, &player.id).await.map(|presence| presence.session_id);
+    Ok(Json(PlayerSessionAnalytics::from_sessions(&sessions, current_session_id.as_deref(), since, now, timezone)))
+}
+
+#[get("/<player_id>/presence")]
+async fn get_player_presence(
+    state: &State<MarsAPIState>, 
+    player_id: &str
+) -> Result<Json<PlayerPresenceResponse>, ApiErrorResponder> {
+    let player = async_extract_player_from_url_v2!(&player_id.to_lowercase(), state);
+    let presence = PlayerPresence::get(state, &player.id).await;
+    Ok(Json(PlayerPresenceResponse { player: player.to_simple(), online: presence.is_some(), presence }))
+}
+
 pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
     rocket_build.mount("/mc/players", routes![
         prelogin, 
@@ -519,6 +861,37 @@ pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
         add_tag_to_player,
         delete_player_tag,
         add_player_rank,
-        delete_player_rank
+        delete_player_rank,
+        get_player_permissions,
+        get_player_rating_history,
+        get_player_maps,
+        search_players,
+        get_player_sessions,
+        get_player_session_analytics,
+        get_player_presence
     ])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{prefix_edit_distance, get_allowed_typos};
+
+    #[test]
+    fn prefix_edit_distance_matches_typos_in_prefixes() {
+        assert_eq!(prefix_edit_distance("notch", "notch"), 0);
+        assert_eq!(prefix_edit_distance("not", "notch"), 0);
+        assert_eq!(prefix_edit_distance("nocth", "notch"), 2);
+        assert_eq!(prefix_edit_distance("notxh", "notch"), 1);
+        assert_eq!(prefix_edit_distance("ntch", "notch_2"), 1);
+        assert_eq!(prefix_edit_distance("notchh", "notch"), 1);
+        // a match in the middle of the name isn't a prefix match
+        assert_eq!(prefix_edit_distance("tch", "notch"), 2);
+    }
+
+    #[test]
+    fn short_queries_allow_no_typos() {
+        assert_eq!(get_allowed_typos("no"), 0);
+        assert_eq!(get_allowed_typos("not"), 1);
+        assert_eq!(get_allowed_typos("notch_"), 2);
+    }
+}
@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
+
+use chrono::{TimeZone, Timelike, Datelike};
+use chrono_tz::Tz;
 
 use serde::{Deserialize, Serialize};
 use rocket::{response::{self, Response, Responder}, Request, http::{Status, ContentType}, serde::json::Json};
 
-use crate::{database::models::{player::{SimplePlayer, Player}, punishment::Punishment, session::Session}, socket::leaderboard::ScoreType};
+use crate::{database::models::{player::{SimplePlayer, Player}, punishment::Punishment, session::Session, level::LevelGamemode, level_rating::LevelRatingAggregate, presence::PlayerPresence}, socket::leaderboard::ScoreType, util::r#macro::unwrap_helper};
 
 #[derive(Deserialize, Serialize)]
 pub struct PlayerPreLoginRequest {
@@ -82,13 +85,51 @@ pub struct PlayerAltResponse {
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct PlayerProfileResponse {
+pub struct PlayerProfile {
+    #[serde(flatten)]
     pub player: Player,
+    pub rank_expirations: Vec<RankExpiration>,
+    pub records_held: RecordsHeld
+}
+
+impl PlayerProfile {
+    pub fn from_player(player: Player, records_held: RecordsHeld) -> Self {
+        let rank_expirations = player.rank_grants.iter().filter_map(|grant| {
+            grant.expires_at.map(|expires_at| RankExpiration { 
+                rank_id: grant.rank_id.clone(), 
+                expires_at, 
+                time_remaining: grant.time_remaining().unwrap_or(0) 
+            })
+        }).collect();
+        PlayerProfile { player, rank_expirations, records_held }
+    }
+}
+
+// map records, current counts the ones nobody has broken yet
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordsHeld {
+    pub current: u64,
+    pub total: u64
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankExpiration {
+    pub rank_id: String,
+    pub expires_at: u64,
+    pub time_remaining: u64
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerProfileResponse {
+    pub player: PlayerProfile,
     pub leaderboard_positions: HashMap<ScoreType, u64>
 }
 
 pub enum PlayerProfileResponder {
-    RawProfile(Player),
+    RawProfile(PlayerProfile),
     ProfileWithLeaderboardPositions(PlayerProfileResponse)
 }
 
@@ -124,3 +165,218 @@ pub struct PlayerAddNoteRequest {
 pub struct PlayerSetActiveTagRequest {
     pub active_tag_id: Option<String>
 }
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerRankGrantRequest {
+    // length of the grant in milliseconds. a new grant is permanent if omitted, a renewed one keeps its expiry
+    #[serde(default)]
+    pub duration: Option<u64>,
+    // turns a renewed timed grant into a permanent one
+    #[serde(default)]
+    pub permanent: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub granter: Option<SimplePlayer>
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPermissionsResponse {
+    pub rank_ids: Vec<String>,
+    pub permissions: Vec<String>,
+    pub prefix: Option<String>
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerMapEntry {
+    pub map_id: String,
+    pub name: String,
+    pub version: String,
+    pub gamemodes: Vec<LevelGamemode>,
+    pub author: bool,
+    pub contribution: Option<String>,
+    pub times_played: u32,
+    pub last_played_at: Option<u64>,
+    pub rating: LevelRatingAggregate
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerMapsResponse {
+    pub maps: Vec<PlayerMapEntry>,
+    pub total_times_played: u64
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSearchResult {
+    pub id: String,
+    pub name: String,
+    // set when the query matched a name the player no longer uses
+    pub matched_name: Option<String>,
+    pub last_joined_at: f64
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSessionPage {
+    pub sessions: Vec<Session>,
+    pub total: u64,
+    pub next_offset: Option<u64>
+}
+
+const MILLIS_PER_HOUR : u64 = 3_600_000;
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSessionAnalytics {
+    pub since: u64,
+    pub timezone: String,
+    pub session_count: u32,
+    pub total_playtime: u64,
+    // finished sessions only
+    pub average_session_length: u64,
+    pub longest_session_length: u64,
+    pub playtime_by_server: HashMap<String, u64>,
+    // keyed by local date, YYYY-MM-DD
+    pub playtime_by_day: BTreeMap<String, u64>,
+    // [weekday from monday][local hour]
+    pub playtime_by_hour_of_week: Vec<Vec<u64>>
+}
+
+impl PlayerSessionAnalytics {
+    // the player's current session counts up to now, any other open session is left stale by a lost logout and
+    // skipped since its length can't be known. everything is clipped to the window
+    pub fn from_sessions(sessions: &Vec<Session>, current_session_id: Option<&str>, since: u64, now: u64, timezone: Tz) -> Self {
+        let sessions : Vec<&Session> = sessions.iter()
+            .filter(|session| !session.is_active() || current_session_id == Some(session.id.as_str()))
+            .collect();
+        let mut analytics = PlayerSessionAnalytics {
+            since,
+            timezone: timezone.name().to_owned(),
+            session_count: sessions.len() as u32,
+            total_playtime: 0,
+            average_session_length: 0,
+            longest_session_length: 0,
+            playtime_by_server: HashMap::new(),
+            playtime_by_day: BTreeMap::new(),
+            playtime_by_hour_of_week: vec![vec![0; 24]; 7]
+        };
+        let finished_lengths : Vec<u64> = sessions.iter().filter_map(|session| session.length()).collect();
+        analytics.average_session_length = finished_lengths.iter().sum::<u64>() / (finished_lengths.len().max(1) as u64);
+        analytics.longest_session_length = finished_lengths.iter().max().cloned().unwrap_or(0);
+
+        for session in sessions.iter() {
+            let start = session.created_at.max(since);
+            let end = session.ended_at.unwrap_or(now).min(now);
+            if end <= start {
+                continue;
+            };
+            analytics.total_playtime += end - start;
+            *analytics.playtime_by_server.entry(session.server_id.clone()).or_insert(0) += end - start;
+
+            // walk local hours so days and the heatmap follow the timezone, including odd offsets
+            let mut cursor = start;
+            while cursor < end {
+                let local = unwrap_helper::return_default!(timezone.timestamp_millis_opt(cursor as i64).single(), analytics);
+                let into_hour = (local.minute() as u64 * 60 + local.second() as u64) * 1000 + local.timestamp_subsec_millis() as u64;
+                let chunk_end = (cursor + MILLIS_PER_HOUR - into_hour).min(end);
+                let chunk = chunk_end - cursor;
+                *analytics.playtime_by_day.entry(local.format("%Y-%m-%d").to_string()).or_insert(0) += chunk;
+                analytics.playtime_by_hour_of_week[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += chunk;
+                cursor = chunk_end;
+            };
+        };
+        analytics
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPresenceResponse {
+    pub player: SimplePlayer,
+    pub online: bool,
+    pub presence: Option<PlayerPresence>
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use crate::database::models::{player::SimplePlayer, session::Session};
+
+    use super::PlayerSessionAnalytics;
+
+    const MINUTE : u64 = 60_000;
+    const HOUR : u64 = 60 * MINUTE;
+    // 2024-01-01T00:00:00Z, a monday
+    const MONDAY : u64 = 1_704_067_200_000;
+
+    fn session(id: &str, server_id: &str, created_at: u64, ended_at: Option<u64>) -> Session {
+        Session {
+            id: id.to_string(),
+            ip: String::from("127.0.0.1"),
+            player: SimplePlayer { name: String::from("Player"), id: String::from("player") },
+            server_id: server_id.to_string(),
+            created_at,
+            ended_at
+        }
+    }
+
+    #[test]
+    fn stale_open_sessions_are_skipped() {
+        let now = MONDAY + 10 * HOUR;
+        let sessions = vec![
+            session("ghost", "lobby", MONDAY, None),
+            session("finished", "lobby", MONDAY + HOUR, Some(MONDAY + 2 * HOUR)),
+            session("current", "pvp", MONDAY + 9 * HOUR, None)
+        ];
+        let analytics = PlayerSessionAnalytics::from_sessions(&sessions, Some("current"), MONDAY, now, Tz::UTC);
+        assert_eq!(analytics.session_count, 2);
+        assert_eq!(analytics.total_playtime, 2 * HOUR);
+        assert_eq!(analytics.playtime_by_server.get("lobby"), Some(&HOUR));
+        assert_eq!(analytics.playtime_by_server.get("pvp"), Some(&HOUR));
+        // only finished sessions have a length
+        assert_eq!(analytics.average_session_length, HOUR);
+        assert_eq!(analytics.longest_session_length, HOUR);
+
+        let analytics = PlayerSessionAnalytics::from_sessions(&sessions, None, MONDAY, now, Tz::UTC);
+        assert_eq!(analytics.session_count, 1);
+        assert_eq!(analytics.total_playtime, HOUR);
+    }
+
+    #[test]
+    fn playtime_is_clipped_to_the_window() {
+        let sessions = vec![
+            session("before", "lobby", MONDAY - 2 * HOUR, Some(MONDAY - HOUR)),
+            session("straddling", "lobby", MONDAY - HOUR, Some(MONDAY + 30 * MINUTE))
+        ];
+        let analytics = PlayerSessionAnalytics::from_sessions(&sessions, None, MONDAY, MONDAY + HOUR, Tz::UTC);
+        assert_eq!(analytics.total_playtime, 30 * MINUTE);
+        assert_eq!(analytics.playtime_by_day.get("2024-01-01"), Some(&(30 * MINUTE)));
+    }
+
+    #[test]
+    fn playtime_follows_the_timezone() {
+        // 23:30 to 00:30 utc, monday into tuesday
+        let sessions = vec![session("late", "lobby", MONDAY + 23 * HOUR + 30 * MINUTE, Some(MONDAY + 24 * HOUR + 30 * MINUTE))];
+        let now = MONDAY + 48 * HOUR;
+
+        let utc = PlayerSessionAnalytics::from_sessions(&sessions, None, MONDAY, now, Tz::UTC);
+        assert_eq!(utc.playtime_by_day.get("2024-01-01"), Some(&(30 * MINUTE)));
+        assert_eq!(utc.playtime_by_day.get("2024-01-02"), Some(&(30 * MINUTE)));
+        assert_eq!(utc.playtime_by_hour_of_week[0][23], 30 * MINUTE);
+        assert_eq!(utc.playtime_by_hour_of_week[1][0], 30 * MINUTE);
+
+        // +05:30, so the same session is 05:00 to 06:00 on tuesday
+        let kolkata = PlayerSessionAnalytics::from_sessions(&sessions, None, MONDAY, now, Tz::Asia__Kolkata);
+        assert_eq!(kolkata.timezone, "Asia/Kolkata");
+        assert_eq!(kolkata.playtime_by_day.len(), 1);
+        assert_eq!(kolkata.playtime_by_day.get("2024-01-02"), Some(&HOUR));
+        assert_eq!(kolkata.playtime_by_hour_of_week[1][5], HOUR);
+        assert_eq!(kolkata.playtime_by_hour_of_week.iter().flatten().sum::<u64>(), HOUR);
+    }
+}
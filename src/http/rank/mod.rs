@@ -21,11 +21,16 @@ async fn create_rank(
         return Err(ApiErrorResponder::rank_confict());
     };
 
+    let rank_id = Uuid::new_v4().to_string();
+    let mut inherits = data.inherits;
+    inherits.dedup();
+    validate_inheritance(state, &rank_id, &inherits).await?;
+
     let lowercase_name = data.name.to_lowercase();
     let mut perms = data.permissions;
     perms.dedup();
     let rank = Rank { 
-        id: Uuid::new_v4().to_string(), 
+        id: rank_id, 
         name: data.name, 
         name_lower: lowercase_name, 
         display_name: data.display_name, 
@@ -34,7 +39,8 @@ async fn create_rank(
         permissions: perms, 
         staff: data.staff, 
         apply_on_join: data.apply_on_join, 
-        created_at: get_u64_time_millis() as f64 
+        created_at: get_u64_time_millis() as f64,
+        inherits
     };
 
     state.database.save(&rank).await;
@@ -64,6 +70,8 @@ async fn delete_rank(state: &State<MarsAPIState>, rank_id: &str, _auth_guard: Au
         return Err(ApiErrorResponder::missing_rank());
     };
 
+    let _ = state.database.ranks.update_many(doc! {"inherits": rank_id}, doc! {"$pull": {"inherits": rank_id}}, None).await;
+
     // we love loading every player into memory
     let mut players_with_rank = Database::consume_cursor_into_owning_vec_option(state.database.players.find(doc! {"rankIds": rank_id}, None).await.ok()).await;
     // compute this early before vector swap remove
@@ -72,7 +80,7 @@ async fn delete_rank(state: &State<MarsAPIState>, rank_id: &str, _auth_guard: Au
     let mut cache_updates : Vec<_> = Vec::new();
     for i in 0..players_with_rank.len() {
         let mut player = players_with_rank.swap_remove(i); // move out of vector
-        player.rank_ids.retain(|existing_rank_id| existing_rank_id != rank_id);
+        player.remove_rank(rank_id);
         // move player into closure, then move closure into vector
         let wrapper = |player: Player| async move {
             state.player_cache.set(&state.database, &player.name, &player, true).await;
@@ -99,6 +107,15 @@ async fn update_rank(
         return Err(ApiErrorResponder::rank_confict());
     };
 
+    let inherits = match data.inherits {
+        Some(mut inherits) => {
+            inherits.dedup();
+            validate_inheritance(state, &existing_rank.id, &inherits).await?;
+            inherits
+        },
+        None => existing_rank.inherits
+    };
+
     let rank_lower_name = data.name.to_lowercase();
     let mut perms = data.permissions;
     perms.dedup();
@@ -112,13 +129,25 @@ async fn update_rank(
         permissions: perms, 
         staff: data.staff, 
         apply_on_join: data.apply_on_join, 
-        created_at: existing_rank.created_at
+        created_at: existing_rank.created_at,
+        inherits
     };
 
     state.database.save(&updated_rank).await;
     Ok(Json(updated_rank))
 }
 
+async fn validate_inheritance(state: &MarsAPIState, rank_id: &str, inherits: &Vec<String>) -> Result<(), ApiErrorResponder> {
+    let ranks = state.database.get_all_documents::<Rank>().await;
+    if inherits.iter().any(|parent_id| !ranks.iter().any(|rank| &rank.id == parent_id)) {
+        return Err(ApiErrorResponder::missing_rank());
+    };
+    if Rank::creates_inheritance_cycle(&ranks, rank_id, inherits) {
+        return Err(ApiErrorResponder::rank_inheritance_cycle());
+    };
+    Ok(())
+}
+
 pub fn mount(rocket: Rocket<Build>) -> Rocket<Build>  {
     rocket.mount("/mc/ranks", routes![create_rank, get_ranks, get_rank_by_id, delete_rank, update_rank])
 }
@@ -12,7 +12,9 @@ pub struct RankCreateRequest {
     #[serde(default)]
     pub staff: bool,
     #[serde(default)]
-    pub apply_on_join: bool
+    pub apply_on_join: bool,
+    #[serde(default)]
+    pub inherits: Vec<String>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,5 +28,8 @@ pub struct RankUpdateRequest {
     pub prefix: Option<String>,
     pub permissions: Vec<String>,
     pub staff: bool,
-    pub apply_on_join: bool
+    pub apply_on_join: bool,
+    // the rank keeps its current parents when left out
+    #[serde(default)]
+    pub inherits: Option<Vec<String>>
 }
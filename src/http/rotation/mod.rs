@@ -0,0 +1,124 @@
+use mongodb::results::DeleteResult;
+use rocket::{Rocket, Build, State, serde::json::Json};
+use uuid::Uuid;
+
+use crate::{MarsAPIState, database::{Database, models::{rotation::{Rotation, DEFAULT_RECENT_EXCLUSION}, level::Level}}, util::{auth::AuthorizationToken, error::ApiErrorResponder, responder::JsonResponder, time::get_u64_time_millis, r#macro::unwrap_helper}};
+
+use self::payload::RotationSaveRequest;
+
+mod payload;
+
+async fn validate_rotation(state: &MarsAPIState, save_req: &RotationSaveRequest) -> Result<(), ApiErrorResponder> {
+    if save_req.maps.is_empty() {
+        return Err(ApiErrorResponder::validation_error_with_message("Rotation must contain at least one map"));
+    };
+    if save_req.max_players.map_or(false, |max_players| max_players < save_req.min_players) {
+        return Err(ApiErrorResponder::validation_error_with_message("Maximum players must not be below minimum players"));
+    };
+    for map in save_req.maps.iter() {
+        if Database::find_by_id::<Level>(&state.database.levels, &map.map_id).await.is_none() {
+            return Err(ApiErrorResponder::missing_map());
+        };
+    };
+    Ok(())
+}
+
+#[post("/", format = "json", data = "<save_req>")]
+async fn create_rotation(
+    state: &State<MarsAPIState>,
+    save_req: Json<RotationSaveRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Rotation>, ApiErrorResponder> {
+    if state.database.find_by_id_or_name::<Rotation>(&save_req.name).await.is_some() {
+        return Err(ApiErrorResponder::rotation_conflict());
+    };
+    validate_rotation(state, &save_req).await?;
+
+    let data = save_req.0;
+    let time_millis = get_u64_time_millis();
+    let rotation = Rotation {
+        id: Uuid::new_v4().to_string(),
+        name_lower: data.name.to_lowercase(),
+        name: data.name,
+        kind: data.kind,
+        maps: data.maps,
+        server_ids: data.server_ids.into_iter().map(|server_id| server_id.to_lowercase()).collect(),
+        min_players: data.min_players,
+        max_players: data.max_players,
+        recent_exclusion: data.recent_exclusion.unwrap_or(DEFAULT_RECENT_EXCLUSION),
+        created_at: time_millis,
+        updated_at: time_millis
+    };
+    state.database.save(&rotation).await;
+    Ok(JsonResponder::created(rotation))
+}
+
+#[get("/")]
+async fn get_rotations(state: &State<MarsAPIState>) -> Json<Vec<Rotation>> {
+    Json(state.database.get_all_documents::<Rotation>().await)
+}
+
+#[get("/<rotation_id>")]
+async fn get_rotation(
+    state: &State<MarsAPIState>,
+    rotation_id: &str
+) -> Result<JsonResponder<Rotation>, ApiErrorResponder> {
+    Ok(JsonResponder::ok(unwrap_helper::return_default!(
+        state.database.find_by_id_or_name::<Rotation>(rotation_id).await,
+        Err(ApiErrorResponder::missing_rotation())
+    )))
+}
+
+#[put("/<rotation_id>", format = "json", data = "<save_req>")]
+async fn update_rotation(
+    state: &State<MarsAPIState>,
+    rotation_id: &str,
+    save_req: Json<RotationSaveRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<Rotation>, ApiErrorResponder> {
+    let mut rotation = unwrap_helper::return_default!(
+        state.database.find_by_id_or_name::<Rotation>(rotation_id).await,
+        Err(ApiErrorResponder::missing_rotation())
+    );
+    if let Some(existing) = state.database.find_by_id_or_name::<Rotation>(&save_req.name).await {
+        if existing.id != rotation.id {
+            return Err(ApiErrorResponder::rotation_conflict());
+        };
+    };
+    validate_rotation(state, &save_req).await?;
+
+    let data = save_req.0;
+    rotation.name_lower = data.name.to_lowercase();
+    rotation.name = data.name;
+    rotation.kind = data.kind;
+    rotation.maps = data.maps;
+    rotation.server_ids = data.server_ids.into_iter().map(|server_id| server_id.to_lowercase()).collect();
+    rotation.min_players = data.min_players;
+    rotation.max_players = data.max_players;
+    rotation.recent_exclusion = data.recent_exclusion.unwrap_or(rotation.recent_exclusion);
+    rotation.updated_at = get_u64_time_millis();
+    state.database.save(&rotation).await;
+    Ok(JsonResponder::ok(rotation))
+}
+
+#[delete("/<rotation_id>")]
+async fn delete_rotation(
+    state: &State<MarsAPIState>,
+    rotation_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<(), ApiErrorResponder> {
+    match state.database.delete_by_id::<Rotation>(rotation_id).await {
+        Some(DeleteResult { deleted_count: 0, .. }) | None => Err(ApiErrorResponder::missing_rotation()),
+        _ => Ok(())
+    }
+}
+
+pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
+    rocket_build.mount("/mc/rotations", routes![
+        create_rotation,
+        get_rotations,
+        get_rotation,
+        update_rotation,
+        delete_rotation
+    ])
+}
@@ -0,0 +1,19 @@
+use serde::{Serialize, Deserialize};
+
+use crate::database::models::rotation::{RotationKind, RotationMap};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationSaveRequest {
+    pub name: String,
+    pub kind: RotationKind,
+    pub maps: Vec<RotationMap>,
+    #[serde(default)]
+    pub server_ids: Vec<String>,
+    #[serde(default)]
+    pub min_players: u32,
+    #[serde(default)]
+    pub max_players: Option<u32>,
+    #[serde(default)]
+    pub recent_exclusion: Option<u32>
+}
@@ -1,8 +1,10 @@
 use futures::future::join_all;
-use mongodb::bson::doc;
+use mongodb::{bson::doc, options::FindOptions};
+use rand::seq::SliceRandom;
 use rocket::{Rocket, Build, State, http::Status, serde::json::Json};
+use uuid::Uuid;
 
-use crate::{MarsAPIState, util::{auth::AuthorizationToken, error::ApiErrorResponder, time::get_u64_time_millis, r#macro::unwrap_helper, responder::JsonResponder}, database::{models::{r#match::Match, session::Session, player::Player, server::ServerEvents}, Database}, http::server::payloads::{ServerStatusResponse, XPMultiplierRequest}};
+use crate::{MarsAPIState, util::{auth::AuthorizationToken, error::ApiErrorResponder, time::get_u64_time_millis, r#macro::unwrap_helper, responder::JsonResponder}, database::{models::{r#match::Match, session::Session, player::Player, server::ServerEvents, rotation::{Rotation, RotationKind, ServerRotationPosition, MapVoteSession, MapVoteOption, VOTE_SESSION_GRACE_MS}, level::Level, presence::PlayerPresence}, Database}, http::server::payloads::{ServerStatusResponse, XPMultiplierRequest, RotationNextResponse, MapVoteStartRequest, MapVoteCastRequest, MapVoteStatusResponse, MapVoteResultResponse}};
 
 pub mod payloads;
 
@@ -15,6 +17,8 @@ async fn server_startup(
     if server_id != auth_guard.server_id {
         return Err(ApiErrorResponder::unauthorized());
     };
+    // players log in again once the server is back up
+    PlayerPresence::clear_server(state, server_id).await;
 
     let last_alive_key = format!("server:{}:last_alive_time", server_id);
     let last_alive_time = state.redis.get_unchecked::<u64>(&last_alive_key).await;
@@ -102,9 +106,8 @@ async fn server_events(
     server_id: &str
 ) -> Result<JsonResponder<ServerEvents>, ApiErrorResponder> {
     let server_id = server_id.to_lowercase();
-    let events : ServerEvents = state.redis.get_unchecked(&format!("server:{}:events", server_id)).await.unwrap_or(ServerEvents { 
-        xp_multiplier: None  
-    });
+    let mut events = ServerEvents::get(state, &server_id).await;
+    events.xp_multiplier = ServerEvents::get_active_xp_multiplier(state, &server_id).await;
     Ok(JsonResponder::ok(events))
 }
 
@@ -118,14 +121,190 @@ async fn xp_multiplier_event(
     if server_id != auth_guard.server_id {
         return Err(ApiErrorResponder::unauthorized());
     };
-    let mut events : ServerEvents = state.redis.get_unchecked(&format!("server:{}:events", server_id)).await.unwrap_or(ServerEvents { 
-        xp_multiplier: None  
-    });
+    // anything below 1 (or NaN) would round every xp gain down to nothing
+    if xp_multiplier_request.value.is_nan() || xp_multiplier_request.value < 1f32 {
+        return Err(ApiErrorResponder::validation_error_with_message("Multiplier must be at least 1"));
+    };
+    let mut events = ServerEvents::get(state, server_id).await;
     events.xp_multiplier = if xp_multiplier_request.value == 1f32 { None } else { Some(xp_multiplier_request.to_xp_multiplier()) };
     state.redis.set(&format!("server:{}:events", server_id), &events).await;
+    state.xp_multipliers.invalidate().await;
     Ok(JsonResponder::ok(events))
 }
 
+const DEFAULT_VOTE_OPTIONS : usize = 5;
+const DEFAULT_VOTE_DURATION_MS : u64 = 30_000;
+const MIN_VOTE_DURATION_MS : u64 = 5_000;
+const MAX_VOTE_DURATION_MS : u64 = 600_000;
+
+// most recently loaded first
+async fn get_recent_map_ids(state: &MarsAPIState, server_id: &str, count: u32) -> Vec<String> {
+    if count == 0 {
+        return Vec::new();
+    };
+    let opts = FindOptions::builder().sort(doc! { "loadedAt": -1 }).limit(count as i64).build();
+    Database::consume_cursor_into_owning_vec_option(state.database.matches.find(doc! { "serverId": server_id }, opts).await.ok()).await
+        .into_iter().map(|recent_match| recent_match.level.id).collect()
+}
+
+async fn get_server_rotation(state: &MarsAPIState, server_id: &str, players: u32) -> Result<Rotation, ApiErrorResponder> {
+    let rotations = state.database.get_all_documents::<Rotation>().await;
+    Rotation::select(rotations, server_id, players).ok_or(ApiErrorResponder::missing_rotation())
+}
+
+async fn get_vote_option(state: &MarsAPIState, map_id: &str) -> Result<MapVoteOption, ApiErrorResponder> {
+    let map = unwrap_helper::return_default!(Database::find_by_id::<Level>(&state.database.levels, map_id).await, Err(ApiErrorResponder::missing_map()));
+    Ok(MapVoteOption { map_id: map.id, name: map.name })
+}
+
+#[get("/<server_id>/rotation/next?<players>")]
+async fn get_next_rotation_map(
+    state: &State<MarsAPIState>,
+    server_id: &str,
+    players: Option<u32>,
+    auth_guard: AuthorizationToken
+) -> Result<JsonResponder<RotationNextResponse>, ApiErrorResponder> {
+    if server_id != auth_guard.server_id {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let rotation = get_server_rotation(state, server_id, players.unwrap_or(0)).await?;
+    let recent_map_ids = get_recent_map_ids(state, server_id, rotation.recent_exclusion).await;
+
+    let position_key = ServerRotationPosition::get_key(server_id);
+    let position = match state.redis.get_unchecked::<ServerRotationPosition>(&position_key).await {
+        Some(stored) if stored.rotation_id == rotation.id => stored.position,
+        _ => 0
+    };
+    let (next_map, next_position) = unwrap_helper::return_default!(
+        rotation.pick_next(position, &recent_map_ids), 
+        Err(ApiErrorResponder::missing_map())
+    );
+    if rotation.kind == RotationKind::Ordered {
+        state.redis.set(&position_key, &ServerRotationPosition { rotation_id: rotation.id.clone(), position: next_position }).await;
+    };
+    Ok(JsonResponder::ok(RotationNextResponse {
+        rotation_id: rotation.id,
+        rotation_name: rotation.name,
+        map: get_vote_option(state, &next_map.map_id).await?
+    }))
+}
+
+#[post("/<server_id>/votes", format = "json", data = "<start_req>")]
+async fn start_map_vote(
+    state: &State<MarsAPIState>,
+    server_id: &str,
+    start_req: Json<MapVoteStartRequest>,
+    auth_guard: AuthorizationToken
+) -> Result<JsonResponder<MapVoteSession>, ApiErrorResponder> {
+    if server_id != auth_guard.server_id {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let time_millis = get_u64_time_millis();
+    if let Some(existing) = MapVoteSession::get(state, server_id).await {
+        if existing.ends_at > time_millis {
+            return Err(ApiErrorResponder::vote_session_running());
+        };
+        existing.clear(state).await;
+    };
+
+    let data = start_req.0;
+    let option_count = data.option_count.unwrap_or(DEFAULT_VOTE_OPTIONS).max(1);
+    let map_ids : Vec<String> = match data.map_ids {
+        Some(map_ids) => map_ids,
+        None => {
+            let rotation = get_server_rotation(state, server_id, data.players.unwrap_or(0)).await?;
+            let recent_map_ids = get_recent_map_ids(state, server_id, rotation.recent_exclusion).await;
+            let candidates = rotation.get_candidates(&recent_map_ids);
+            candidates.choose_multiple(&mut rand::thread_rng(), option_count).map(|map| map.map_id.clone()).collect()
+        }
+    };
+    let mut options : Vec<MapVoteOption> = Vec::new();
+    for map_id in map_ids.iter() {
+        if options.iter().any(|option| &option.map_id == map_id) {
+            continue;
+        };
+        options.push(get_vote_option(state, map_id).await?);
+    };
+    if options.len() < 2 {
+        return Err(ApiErrorResponder::validation_error_with_message("A map vote needs at least two maps"));
+    };
+
+    let duration = data.duration.unwrap_or(DEFAULT_VOTE_DURATION_MS).clamp(MIN_VOTE_DURATION_MS, MAX_VOTE_DURATION_MS);
+    let session = MapVoteSession {
+        id: Uuid::new_v4().to_string(),
+        server_id: server_id.to_owned(),
+        options,
+        started_at: time_millis,
+        ends_at: time_millis + duration
+    };
+    state.redis.set_with_expiry(&MapVoteSession::get_key(server_id), &session, Some((duration + VOTE_SESSION_GRACE_MS) as usize)).await;
+    Ok(JsonResponder::created(session))
+}
+
+#[get("/<server_id>/votes")]
+async fn get_map_vote(
+    state: &State<MarsAPIState>,
+    server_id: &str
+) -> Result<JsonResponder<MapVoteStatusResponse>, ApiErrorResponder> {
+    let server_id = server_id.to_lowercase();
+    let session = unwrap_helper::return_default!(MapVoteSession::get(state, &server_id).await, Err(ApiErrorResponder::missing_vote_session()));
+    let tallies = session.get_tallies(state).await;
+    Ok(JsonResponder::ok(MapVoteStatusResponse { session, tallies }))
+}
+
+#[post("/<server_id>/votes/cast", format = "json", data = "<cast_req>")]
+async fn cast_map_vote(
+    state: &State<MarsAPIState>,
+    server_id: &str,
+    cast_req: Json<MapVoteCastRequest>,
+    auth_guard: AuthorizationToken
+) -> Result<JsonResponder<MapVoteStatusResponse>, ApiErrorResponder> {
+    if server_id != auth_guard.server_id {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let session = unwrap_helper::return_default!(MapVoteSession::get(state, server_id).await, Err(ApiErrorResponder::missing_vote_session()));
+    if session.ends_at <= get_u64_time_millis() {
+        return Err(ApiErrorResponder::missing_vote_session());
+    };
+    if !session.options.iter().any(|option| option.map_id == cast_req.map_id) {
+        return Err(ApiErrorResponder::validation_error_with_message("The map is not an option in this vote"));
+    };
+    session.cast(state, &cast_req.player.id, &cast_req.map_id).await;
+    let tallies = session.get_tallies(state).await;
+    Ok(JsonResponder::ok(MapVoteStatusResponse { session, tallies }))
+}
+
+// closes the vote early if it's still running; ties are broken randomly
+#[post("/<server_id>/votes/end")]
+async fn end_map_vote(
+    state: &State<MarsAPIState>,
+    server_id: &str,
+    auth_guard: AuthorizationToken
+) -> Result<JsonResponder<MapVoteResultResponse>, ApiErrorResponder> {
+    if server_id != auth_guard.server_id {
+        return Err(ApiErrorResponder::unauthorized());
+    };
+    let session = unwrap_helper::return_default!(MapVoteSession::get(state, server_id).await, Err(ApiErrorResponder::missing_vote_session()));
+    let tallies = session.get_tallies(state).await;
+    session.clear(state).await;
+
+    let most_votes = tallies.values().max().cloned().unwrap_or(0);
+    let leaders : Vec<&MapVoteOption> = session.options.iter().filter(|option| tallies.get(&option.map_id).cloned().unwrap_or(0) == most_votes).collect();
+    let winner = unwrap_helper::return_default!(leaders.choose(&mut rand::thread_rng()).cloned(), Err(ApiErrorResponder::missing_vote_session())).clone();
+    info!("Map vote on '{}' won by {} with {} votes", server_id, winner.name, most_votes);
+    Ok(JsonResponder::ok(MapVoteResultResponse { session, tallies, winner }))
+}
+
 pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
-    rocket_build.mount("/mc/servers", routes![server_startup, server_status, server_events, xp_multiplier_event])
+    rocket_build.mount("/mc/servers", routes![
+        server_startup, 
+        server_status, 
+        server_events, 
+        xp_multiplier_event,
+        get_next_rotation_map,
+        start_map_vote,
+        get_map_vote,
+        cast_map_vote,
+        end_map_vote
+    ])
 }
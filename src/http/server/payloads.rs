@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use serde::{Serialize, Deserialize};
 
-use crate::{database::models::{r#match::Match, player::SimplePlayer, server::XPMultiplier}, util::time::get_u64_time_millis};
+use crate::{database::models::{r#match::Match, player::SimplePlayer, server::XPMultiplier, rotation::{MapVoteOption, MapVoteSession}}, util::time::get_u64_time_millis};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -26,3 +28,48 @@ impl XPMultiplierRequest {
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationNextResponse {
+    pub rotation_id: String,
+    pub rotation_name: String,
+    pub map: MapVoteOption
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapVoteStartRequest {
+    // drawn from the server's rotation when absent
+    #[serde(default)]
+    pub map_ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub option_count: Option<usize>,
+    #[serde(default)]
+    pub players: Option<u32>,
+    // milliseconds, kept between 5 seconds and 10 minutes
+    #[serde(default)]
+    pub duration: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapVoteCastRequest {
+    pub player: SimplePlayer,
+    pub map_id: String
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapVoteStatusResponse {
+    pub session: MapVoteSession,
+    pub tallies: HashMap<String, u32>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapVoteResultResponse {
+    pub session: MapVoteSession,
+    pub tallies: HashMap<String, u32>,
+    pub winner: MapVoteOption
+}
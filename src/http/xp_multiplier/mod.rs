@@ -0,0 +1,72 @@
+use mongodb::{bson::doc, results::DeleteResult};
+use rocket::{Rocket, Build, State, serde::json::Json};
+use uuid::Uuid;
+
+use crate::{MarsAPIState, database::{Database, models::server::XPMultiplierWindow}, util::{auth::AuthorizationToken, error::ApiErrorResponder, responder::JsonResponder, time::get_u64_time_millis}};
+
+use self::payload::XPMultiplierWindowCreateRequest;
+
+mod payload;
+
+#[post("/", format = "json", data = "<create_req>")]
+async fn create_xp_multiplier_window(
+    state: &State<MarsAPIState>,
+    create_req: Json<XPMultiplierWindowCreateRequest>,
+    _auth_guard: AuthorizationToken
+) -> Result<JsonResponder<XPMultiplierWindow>, ApiErrorResponder> {
+    let data = create_req.0;
+    let time_millis = get_u64_time_millis();
+    let starts_at = data.starts_at.unwrap_or(time_millis);
+    // the highest active multiplier wins, so a penalty below 1 would never apply while another is active. NaN fails too
+    if data.value.is_nan() || data.value < 1f32 {
+        return Err(ApiErrorResponder::validation_error_with_message("Multiplier must be at least 1"));
+    };
+    if data.ends_at <= starts_at || data.ends_at <= time_millis {
+        return Err(ApiErrorResponder::validation_error_with_message("Multiplier must end in the future and after it starts"));
+    };
+
+    let window = XPMultiplierWindow {
+        id: Uuid::new_v4().to_string(),
+        value: data.value,
+        server_id: data.server_id.map(|server_id| server_id.to_lowercase()),
+        starts_at,
+        ends_at: data.ends_at,
+        player: data.player,
+        created_at: time_millis
+    };
+    state.database.save(&window).await;
+    state.xp_multipliers.invalidate().await;
+    info!("Scheduled x{} XP multiplier for {} until {}", window.value, window.server_id.as_deref().unwrap_or("all servers"), window.ends_at);
+    Ok(JsonResponder::created(window))
+}
+
+// active and upcoming windows
+#[get("/")]
+async fn get_xp_multiplier_windows(state: &State<MarsAPIState>) -> Json<Vec<XPMultiplierWindow>> {
+    Json(Database::consume_cursor_into_owning_vec_option(state.database.xp_multiplier_windows.find(doc! {
+        "endsAt": {"$gt": get_u64_time_millis() as i64}
+    }, None).await.ok()).await)
+}
+
+#[delete("/<window_id>")]
+async fn delete_xp_multiplier_window(
+    state: &State<MarsAPIState>,
+    window_id: &str,
+    _auth_guard: AuthorizationToken
+) -> Result<(), ApiErrorResponder> {
+    match state.database.delete_by_id::<XPMultiplierWindow>(window_id).await {
+        Some(DeleteResult { deleted_count: 0, .. }) | None => Err(ApiErrorResponder::missing_xp_multiplier()),
+        _ => {
+            state.xp_multipliers.invalidate().await;
+            Ok(())
+        }
+    }
+}
+
+pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
+    rocket_build.mount("/mc/xp_multipliers", routes![
+        create_xp_multiplier_window,
+        get_xp_multiplier_windows,
+        delete_xp_multiplier_window
+    ])
+}
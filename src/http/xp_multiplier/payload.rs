@@ -0,0 +1,16 @@
+use serde::{Serialize, Deserialize};
+
+use crate::database::models::player::SimplePlayer;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XPMultiplierWindowCreateRequest {
+    pub value: f32,
+    #[serde(default)]
+    pub server_id: Option<String>,
+    #[serde(default)]
+    pub starts_at: Option<u64>,
+    pub ends_at: u64,
+    #[serde(default)]
+    pub player: Option<SimplePlayer>
+}
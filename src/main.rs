@@ -4,9 +4,9 @@ use std::{marker::PhantomData, sync::Arc, env, net::{Ipv4Addr, IpAddr}};
 
 use anyhow::anyhow;
 use config::{deserialize_mars_config, MarsConfig};
-use database::{Database, cache::{Cache, get_redis_pool, RedisAdapter}, models::{player::Player, r#match::Match}};
+use database::{Database, cache::{Cache, get_redis_pool, RedisAdapter}, models::{player::Player, r#match::Match, server::XPMultiplierCache}};
 use rocket::{Build, Rocket, Shutdown, Config, figment::Figment};
-use socket::leaderboard::MarsLeaderboards;
+use socket::{leaderboard::MarsLeaderboards, server::server_outbox::ServerOutbox};
 
 use crate::socket::socket_handler::{SocketState, setup_socket};
 
@@ -15,6 +15,7 @@ mod config;
 mod database;
 mod http;
 mod socket;
+mod task;
 
 fn setup_logger() -> Result<(), fern::InitError> {
     fern::Dispatch::new()
@@ -44,6 +45,8 @@ pub struct MarsAPIState {
     pub player_cache: Arc<Cache<Player>>,
     pub match_cache: Arc<Cache<Match>>,
     pub leaderboards: Arc<MarsLeaderboards>,
+    pub server_outbox: Arc<ServerOutbox>,
+    pub xp_multipliers: Arc<XPMultiplierCache>,
 }
 
 fn rocket(state: MarsAPIState) -> Rocket<Build> {
@@ -60,7 +63,11 @@ fn rocket(state: MarsAPIState) -> Rocket<Build> {
         &http::perks::mount,
         &http::leaderboard::mount,
         &http::report::mount,
-        &http::r#match::mount
+        &http::r#match::mount,
+        &http::xp_multiplier::mount,
+        &http::balance::mount,
+        &http::rotation::mount,
+        &http::online::mount
     ];
     let is_debug = env::var("MARS_DEBUG").unwrap_or("false".to_owned()).parse::<bool>().unwrap_or(false);
     let http_port = env::var("MARS_HTTP_PORT").unwrap_or("8000".to_owned()).parse::<u32>().unwrap_or(8000);
@@ -154,7 +161,7 @@ async fn main() -> Result<(), String> {
     });
 
     // leaderboards
-    let leaderboards = Arc::new(MarsLeaderboards::new(Arc::clone(&redis_adapter), Arc::clone(&database)));
+    let leaderboards = Arc::new(MarsLeaderboards::new(Arc::clone(&redis_adapter), Arc::clone(&database), mars_config.options.leaderboard_periods));
 
     // immutable state for rocket to manage
     let state = MarsAPIState { 
@@ -163,9 +170,16 @@ async fn main() -> Result<(), String> {
         redis: Arc::clone(&redis_adapter), 
         player_cache, 
         match_cache,
-        leaderboards
+        leaderboards,
+        server_outbox: Arc::new(ServerOutbox::new()),
+        xp_multipliers: Arc::new(XPMultiplierCache::new())
     };
 
+    tokio::spawn(task::rank_expiry::run_rank_expiry_sweeper(state.clone()));
+    tokio::spawn(task::xp_multiplier_expiry::run_xp_multiplier_expiry_sweeper(state.clone()));
+    tokio::spawn(task::leaderboard_archive::run_leaderboard_archiver(state.clone()));
+    tokio::spawn(task::ratio_leaderboards::run_ratio_leaderboard_recomputer(state.clone()));
+
     let ws_port = env::var("MARS_WS_PORT").unwrap_or("7000".to_owned()).parse::<u32>().unwrap_or(7000);
     let res = tokio::try_join!(
         setup_rocket(state.clone()), 
@@ -32,6 +32,7 @@ pub enum EventType {
 
     // plugin-bound
     PlayerXpGain,
+    PlayerLevelUp,
     ForceMatchEnd,
     Message,
     DisconnectPlayer
@@ -1,50 +1,62 @@
-use crate::{socket::{player::{player_listener::PlayerListener, player_events::PlayerDeathData}, participant::participant_context::{PlayerMatchResult}, r#match::match_events::{MatchEndData}, server::server_context::ServerContext}, database::models::{participant::Participant, r#match::Match}};
+use crate::{socket::{leaderboard::{LeaderboardBatch, LeaderboardScope, ScoreType}, player::{player_listener::PlayerListener, player_events::PlayerDeathData}, participant::participant_context::{PlayerMatchResult}, r#match::match_events::{MatchEndData}, server::server_context::ServerContext}, database::models::{participant::Participant, r#match::Match}};
 
 pub struct LeaderboardListener {}
 
-#[async_trait]
-impl PlayerListener for LeaderboardListener {
-    type Context = Participant;
+impl LeaderboardListener {
+    // queues the update on the network-wide board along with the gamemode and map boards of the match
+    fn queue_increment(batch: &mut LeaderboardBatch, server_context: &ServerContext, current_match: &Match, score_type: ScoreType, id: &String, incr: u32) {
+        let leaderboard = score_type.to_leaderboard(&server_context.api_state.leaderboards);
+        leaderboard.queue_increment(batch, id, incr);
+        for scope in LeaderboardScope::of_match(current_match) {
+            leaderboard.scoped(scope).queue_increment(batch, id, incr);
+        };
+    }
 
-    async fn on_match_end_v2(
-        &self,
-        server_context: &mut ServerContext, 
-        current_match: &mut Match, 
-        context: &mut Self::Context, 
-        end_data: &mut MatchEndData
-    ) { 
-        {
-            if !current_match.is_tracking_stats() {
-                return;
-            }
+    async fn increment(server_context: &ServerContext, current_match: &Match, score_type: ScoreType, id: &String, incr: Option<u32>) {
+        let mut batch = LeaderboardBatch::new();
+        Self::queue_increment(&mut batch, server_context, current_match, score_type, id, incr.unwrap_or(1));
+        batch.submit(&server_context.api_state.redis).await;
+    }
 
-            let match_result = current_match.get_participant_match_result(&context, end_data);
+    async fn set_if_higher(server_context: &ServerContext, current_match: &Match, score_type: ScoreType, id: &String, new: u32) {
+        let leaderboard = score_type.to_leaderboard(&server_context.api_state.leaderboards);
+        let mut batch = LeaderboardBatch::new();
+        leaderboard.queue_set_if_higher(&mut batch, id, new);
+        for scope in LeaderboardScope::of_match(current_match) {
+            leaderboard.scoped(scope).queue_set_if_higher(&mut batch, id, new);
+        };
+        batch.submit(&server_context.api_state.redis).await;
+    }
 
-            match match_result {
-                PlayerMatchResult::Win => {
-                    server_context.api_state.leaderboards.wins.increment(&context.get_id_name(), Some(1)).await;
-                },
-                PlayerMatchResult::Lose => {
-                    server_context.api_state.leaderboards.losses.increment(&context.get_id_name(), Some(1)).await;
-                },
-                PlayerMatchResult::Tie => {
-                    server_context.api_state.leaderboards.ties.increment(&context.get_id_name(), Some(1)).await;
-                },
-                _ => {} 
-            }
+    // match end touches every participant, so their updates are sent together rather than per participant
+    pub async fn on_match_end_batch(server_context: &ServerContext, current_match: &Match, end_data: &MatchEndData) {
+        if !current_match.is_tracking_stats() {
+            return;
+        };
 
-            server_context.api_state.leaderboards.matches_played.increment(&context.get_id_name(), Some(1)).await;
-            server_context.api_state.leaderboards.messages_sent.increment(
-                &context.get_id_name(), 
-                Some(context.stats.messages.total())
-            ).await;
-            server_context.api_state.leaderboards.game_playtime.increment(
-                &context.get_id_name(), 
-                Some(u32::try_from(context.stats.game_playtime).unwrap_or(0))
-            ).await;
+        let mut batch = LeaderboardBatch::new();
+        for participant in current_match.participants.values() {
+            let id_name = participant.get_id_name();
+            match current_match.get_participant_match_result(participant, end_data) {
+                PlayerMatchResult::Win => Self::queue_increment(&mut batch, server_context, current_match, ScoreType::Wins, &id_name, 1),
+                PlayerMatchResult::Lose => Self::queue_increment(&mut batch, server_context, current_match, ScoreType::Losses, &id_name, 1),
+                PlayerMatchResult::Tie => Self::queue_increment(&mut batch, server_context, current_match, ScoreType::Ties, &id_name, 1),
+                _ => {}
+            };
+            Self::queue_increment(&mut batch, server_context, current_match, ScoreType::MatchesPlayed, &id_name, 1);
+            Self::queue_increment(&mut batch, server_context, current_match, ScoreType::MessagesSent, &id_name, participant.stats.messages.total());
+            Self::queue_increment(
+                &mut batch, server_context, current_match, ScoreType::GamePlaytime, &id_name, 
+                u32::try_from(participant.stats.game_playtime).unwrap_or(0)
+            );
         };
+        batch.submit(&server_context.api_state.redis).await;
     }
+}
 
+#[async_trait]
+impl PlayerListener for LeaderboardListener {
+    type Context = Participant;
 
     async fn on_kill(
         &self,
@@ -59,9 +71,9 @@ impl PlayerListener for LeaderboardListener {
                 return;
             };
 
-            server_context.api_state.leaderboards.kills.increment(&context.get_id_name(), Some(1)).await;
+            Self::increment(server_context, current_match, ScoreType::Kills, &context.get_id_name(), Some(1)).await;
             if first_blood {
-                server_context.api_state.leaderboards.first_bloods.increment(&context.get_id_name(), Some(1)).await;
+                Self::increment(server_context, current_match, ScoreType::FirstBloods, &context.get_id_name(), Some(1)).await;
             };
         }
     }
@@ -79,7 +91,7 @@ impl PlayerListener for LeaderboardListener {
                 return;
             };
 
-            server_context.api_state.leaderboards.deaths.increment(&context.get_id_name(), Some(1)).await;
+            Self::increment(server_context, current_match, ScoreType::Deaths, &context.get_id_name(), Some(1)).await;
         };
     }
 
@@ -94,7 +106,7 @@ impl PlayerListener for LeaderboardListener {
             if !current_match.is_tracking_stats() {
                 return;
             };
-            server_context.api_state.leaderboards.highest_killstreak.set_if_higher(&context.get_id_name(), amount).await;
+            Self::set_if_higher(server_context, current_match, ScoreType::HighestKillstreak, &context.get_id_name(), amount).await;
         };
     }
 
@@ -109,8 +121,8 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.destroyable_destroys.increment(&context.get_id_name(), Some(1)).await;
-        server_context.api_state.leaderboards.destroyable_block_destroys.increment(&context.get_id_name(), Some(block_count)).await;
+        Self::increment(server_context, current_match, ScoreType::DestroyableDestroys, &context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::DestroyableBlockDestroys, &context.get_id_name(), Some(block_count)).await;
     }
 
     async fn on_core_leak(
@@ -124,8 +136,8 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.core_leaks.increment(&context.get_id_name(), Some(1)).await;
-        server_context.api_state.leaderboards.core_block_destroys.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::CoreLeaks, &context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::CoreBlockDestroys, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_flag_place(
@@ -138,8 +150,8 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.flag_captures.increment(&context.get_id_name(), Some(1)).await;
-        server_context.api_state.leaderboards.flag_hold_time.increment(&context.get_id_name(), Some(u32::try_from(held_time).unwrap())).await;
+        Self::increment(server_context, current_match, ScoreType::FlagCaptures, &context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::FlagHoldTime, &context.get_id_name(), Some(u32::try_from(held_time).unwrap())).await;
     }
 
     async fn on_flag_pickup(
@@ -151,7 +163,7 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.flag_pickups.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::FlagPickups, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_flag_drop(
@@ -164,8 +176,8 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.flag_drops.increment(&context.get_id_name(), Some(1)).await;
-        server_context.api_state.leaderboards.flag_hold_time.increment(&context.get_id_name(), Some(u32::try_from(held_time).unwrap())).await;
+        Self::increment(server_context, current_match, ScoreType::FlagDrops, &context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::FlagHoldTime, &context.get_id_name(), Some(u32::try_from(held_time).unwrap())).await;
     }
 
     async fn on_flag_defend(
@@ -177,7 +189,7 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.flag_defends.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::FlagDefends, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_wool_place(
@@ -190,7 +202,7 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.wool_captures.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::WoolCaptures, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_wool_pickup(
@@ -202,7 +214,7 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.wool_pickups.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::WoolPickups, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_wool_drop(
@@ -215,7 +227,7 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.wool_drops.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::WoolDrops, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_wool_defend(
@@ -227,7 +239,7 @@ impl PlayerListener for LeaderboardListener {
         if !current_match.is_tracking_stats() {
             return;
         };
-        server_context.api_state.leaderboards.wool_defends.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::WoolDefends, &context.get_id_name(), Some(1)).await;
     }
 
     async fn on_control_point_capture(
@@ -241,6 +253,6 @@ impl PlayerListener for LeaderboardListener {
             return;
         };
 
-        server_context.api_state.leaderboards.control_point_captures.increment(&context.get_id_name(), Some(1)).await;
+        Self::increment(server_context, current_match, ScoreType::ControlPointCaptures, &context.get_id_name(), Some(1)).await;
     }
 }
@@ -1,21 +1,58 @@
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use mongodb::{bson::doc, Cursor};
 use num_traits::cast::FromPrimitive;
-use redis::{aio::Connection, ToRedisArgs};
+use redis::{aio::Connection, ToRedisArgs, AsyncCommands, Pipeline};
 use serde::{Serialize, Deserialize};
 use strum_macros::{Display, EnumIter, EnumString};
 use strum::IntoEnumIterator;
 
-use chrono::{Month, DateTime, Utc, TimeZone, FixedOffset, Datelike};
+use chrono::{Month, Utc, Datelike, NaiveDate, Weekday, Duration, TimeZone};
+use chrono_tz::Tz;
 
-use crate::{database::{cache::RedisAdapter, Database, models::player::Player}, util::r#macro::unwrap_helper};
+use self::{ratio_leaderboard::RatioLeaderboards, rating_leaderboard::RatingLeaderboard};
+
+use crate::{database::{cache::RedisAdapter, Database, models::{player::Player, level::LevelGamemode, r#match::Match, leaderboard_snapshot::{LeaderboardSnapshot, LeaderboardSnapshotEntry}}}, util::{r#macro::unwrap_helper, time::get_u64_time_millis}};
 
 pub mod leaderboard_listener;
+pub mod ratio_leaderboard;
+pub mod rating_leaderboard;
+
+const ARCHIVED_LEADERBOARD_SIZE : u32 = 100;
+const ARCHIVED_LEADERBOARD_TTL_SECONDS : u64 = 86_400;
+const REBUILD_BASE_KEY_PREFIX : &'static str = "rebuild:base:";
+const REBUILD_BASE_TTL_SECONDS : u64 = 7 * 86_400;
 
-fn get_est_datetime() -> DateTime<FixedOffset> {
-    let naive_utc_time = Utc::now().naive_utc();
-    let fixed_offset = FixedOffset::west(4 * 3600); // UTC-4 for EST
-    fixed_offset.from_utc_datetime(&naive_utc_time)
+// where and when leaderboard periods roll over, read from config.properties
+#[derive(Clone, Copy)]
+pub struct LeaderboardPeriodSettings {
+    pub timezone: Tz,
+    pub week_start: Weekday,
+    // keeps the zero-based month ids so boards written before the change are still read
+    pub legacy_ids: bool
+}
+
+impl Default for LeaderboardPeriodSettings {
+    fn default() -> Self {
+        LeaderboardPeriodSettings { timezone: Tz::America__New_York, week_start: Weekday::Mon, legacy_ids: true }
+    }
+}
+
+impl LeaderboardPeriodSettings {
+    pub fn today(&self) -> NaiveDate {
+        Utc::now().with_timezone(&self.timezone).date_naive()
+    }
+
+    pub fn date_of(&self, time_millis: u64) -> NaiveDate {
+        self.timezone.timestamp_millis_opt(time_millis as i64).single().map(|date| date.date_naive()).unwrap_or(self.today())
+    }
+
+    // local midnight on january 1st of the current year
+    pub fn start_of_year_millis(&self) -> u64 {
+        let start = NaiveDate::from_ymd_opt(self.today().year(), 1, 1).and_then(|date| date.and_hms_opt(0, 0, 0));
+        start.and_then(|start| self.timezone.from_local_datetime(&start).earliest())
+            .map_or(0, |start| u64::try_from(start.timestamp_millis()).unwrap_or(0))
+    }
 }
 
 pub enum Season {
@@ -45,7 +82,8 @@ impl Season {
     }
 }
 
-#[derive(EnumIter, EnumString)]
+#[derive(EnumIter, EnumString, Serialize, Deserialize, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum LeaderboardPeriod {
     Daily,
@@ -57,41 +95,49 @@ pub enum LeaderboardPeriod {
 }
 
 impl LeaderboardPeriod {
-    pub fn get_today_id(&self) -> String {
-        let date = get_est_datetime();
+    pub fn get_today_id(&self, settings: &LeaderboardPeriodSettings) -> String {
+        self.get_id_for_date(&settings.today(), settings)
+    }
+
+    pub fn get_id_for_date(&self, date: &NaiveDate, settings: &LeaderboardPeriodSettings) -> String {
+        // legacy ids use Java-style zero-based months
+        let month = if settings.legacy_ids { date.month0() } else { date.month() };
         match &self {
-            Self::Daily => {
-                let day = date.day();
-                let month = date.month() - 1; // Java being cringe
-                let year = date.year();
-                format!("{}:d:{}:{}", year, month, day)
-            },
+            Self::Daily => format!("{}:d:{}:{}", date.year(), month, date.day()),
             Self::Weekly => {
-                let week = date.iso_week().week();
-                let year = date.year();
-                format!("{}:w:{}", year, week)
-            },
-            Self::Monthly => {
-                let month = date.month() - 1;
-                let year = date.year();
-                format!("{}:m:{}", year, month)
+                // shift the date so the configured week start lines up with the ISO monday
+                let shift = (7 - settings.week_start.num_days_from_monday()) % 7;
+                let week = (*date + Duration::days(shift as i64)).iso_week();
+                let year = if settings.legacy_ids { date.year() } else { week.year() };
+                format!("{}:w:{}", year, week.week())
             },
+            Self::Monthly => format!("{}:m:{}", date.year(), month),
             Self::Seasonally => {
-                let month = date.month() - 1;
-                let season = Season::of_northern(Month::from_u32(month + 1).unwrap_or(Month::January)).name();
-                let year = date.year();
-                format!("{}:s:{}", year, season)
-            },
-            Self::Yearly => {
-                let year = date.year();
-                format!("{}:y", year)
+                let season = Season::of_northern(Month::from_u32(date.month()).unwrap_or(Month::January)).name();
+                format!("{}:s:{}", date.year(), season)
             },
+            Self::Yearly => format!("{}:y", date.year()),
             Self::AllTime => String::from("all"),
         }
     }
+
+    // inverse of the period marker embedded in ids like 2023:w:12
+    pub fn from_period_id(period_id: &str) -> Option<LeaderboardPeriod> {
+        if period_id == "all" {
+            return Some(Self::AllTime);
+        };
+        match period_id.split(":").nth(1) {
+            Some("d") => Some(Self::Daily),
+            Some("w") => Some(Self::Weekly),
+            Some("m") => Some(Self::Monthly),
+            Some("s") => Some(Self::Seasonally),
+            Some("y") => Some(Self::Yearly),
+            _ => None
+        }
+    }
 }
 
-#[derive(Display, EnumString, Serialize, Deserialize, Clone, Eq, Hash, PartialEq)]
+#[derive(Debug, Display, EnumString, EnumIter, Serialize, Deserialize, Clone, Eq, Hash, PartialEq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum ScoreType {
@@ -156,14 +202,92 @@ impl ScoreType {
     }
 }
 
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub enum LeaderboardScope {
+    Global,
+    Gamemode(LevelGamemode),
+    Map(String)
+}
+
+impl LeaderboardScope {
+    // the gamemode and map boards a match contributes to, besides the global one
+    pub fn of_match(current_match: &Match) -> Vec<LeaderboardScope> {
+        let mut scopes : Vec<LeaderboardScope> = current_match.level.gamemodes.iter().map(|gamemode| {
+            LeaderboardScope::Gamemode(gamemode.clone())
+        }).collect();
+        scopes.push(LeaderboardScope::Map(current_match.level.id.clone()));
+        scopes
+    }
+
+    fn get_key_segment(&self) -> String {
+        match self {
+            Self::Global => String::new(),
+            Self::Gamemode(gamemode) => format!("gm:{}:", gamemode),
+            Self::Map(map_id) => format!("map:{}:", map_id)
+        }
+    }
+
+    // scoped boards aren't archived, so finished periods are left to expire on their own
+    fn get_period_ttl_seconds(&self, period: &LeaderboardPeriod) -> Option<u64> {
+        if let Self::Global = self {
+            return None;
+        };
+        match period {
+            LeaderboardPeriod::Daily => Some(2 * 86_400),
+            LeaderboardPeriod::Weekly => Some(8 * 86_400),
+            LeaderboardPeriod::Monthly => Some(32 * 86_400),
+            LeaderboardPeriod::Seasonally => Some(124 * 86_400),
+            LeaderboardPeriod::Yearly => Some(367 * 86_400),
+            LeaderboardPeriod::AllTime => None
+        }
+    }
+}
+
+// queues leaderboard writes so they reach redis in a single MULTI/EXEC round trip
+pub struct LeaderboardBatch {
+    pipeline: Pipeline,
+    commands: u32
+}
+
+impl LeaderboardBatch {
+    pub fn new() -> Self {
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        LeaderboardBatch { pipeline, commands: 0 }
+    }
+
+    pub async fn submit(self, cache: &RedisAdapter) {
+        if self.commands == 0 {
+            return;
+        };
+        let _ = cache.submit(|mut conn| async move {
+            if let Err(e) = self.pipeline.query_async::<Connection, ()>(&mut conn).await {
+                warn!("Could not submit leaderboard batch: {}", e);
+            };
+        }).await;
+    }
+}
+
 pub struct Leaderboard {
     pub score_type: ScoreType,
+    pub scope: LeaderboardScope,
+    pub periods: LeaderboardPeriodSettings,
     pub database: Arc<Database>,
     pub cache: Arc<RedisAdapter>
 }
 
 
 impl Leaderboard {
+    pub fn scoped(&self, scope: LeaderboardScope) -> Leaderboard {
+        Leaderboard { 
+            score_type: self.score_type.clone(), 
+            scope, 
+            periods: self.periods, 
+            database: Arc::clone(&self.database), 
+            cache: Arc::clone(&self.cache) 
+        }
+    }
+
     async fn zadd_entries<T: ToRedisArgs, K: ToRedisArgs, V: ToRedisArgs>(&self, key: &T, items: &Vec<(K, V)>) {
         let _ = self.cache.submit(|mut conn| async move {
             let _ = redis::cmd("ZADD")
@@ -196,21 +320,73 @@ impl Leaderboard {
     }
 
     pub async fn set(&self, id: &String, score: u32) {
-        let u64_score = score as u64;
-        let _ = self.cache.submit(|mut conn| async move {
-            for period in LeaderboardPeriod::iter() {
-                let _ = redis::cmd("ZADD").arg(&self.get_id(&period)).arg(u64_score).arg(id).query_async::<Connection, ()>(&mut conn).await;
-            };
-        }).await;
+        let mut batch = LeaderboardBatch::new();
+        self.queue_set(&mut batch, id, score);
+        batch.submit(&self.cache).await;
     }
 
     pub async fn increment(&self, id: &String, incr: Option<u32>) {
-        let u64_incr = incr.unwrap_or(1) as u64;
-        let _ = self.cache.submit(|mut conn| async move {
-            for period in LeaderboardPeriod::iter() {
-                let _ = redis::cmd("ZINCRBY").arg(&self.get_id(&period)).arg(u64_incr).arg(id).query_async::<Connection, ()>(&mut conn).await;
-            };
-        }).await;
+        let mut batch = LeaderboardBatch::new();
+        self.queue_increment(&mut batch, id, incr.unwrap_or(1));
+        batch.submit(&self.cache).await;
+    }
+
+    pub fn queue_set(&self, batch: &mut LeaderboardBatch, id: &String, score: u32) {
+        for period in LeaderboardPeriod::iter() {
+            batch.pipeline.cmd("ZADD").arg(&self.get_id(&period)).arg(score as u64).arg(id).ignore();
+            self.queue_expiry(batch, &period);
+        };
+        batch.commands += 1;
+    }
+
+    pub fn queue_increment(&self, batch: &mut LeaderboardBatch, id: &String, incr: u32) {
+        for period in LeaderboardPeriod::iter() {
+            batch.pipeline.cmd("ZINCRBY").arg(&self.get_id(&period)).arg(incr as u64).arg(id).ignore();
+            self.queue_expiry(batch, &period);
+        };
+        batch.commands += 1;
+    }
+
+    // GT only ever raises a score, so concurrent writers can't lower one another's records
+    pub fn queue_set_if_higher(&self, batch: &mut LeaderboardBatch, id: &String, new: u32) {
+        for period in LeaderboardPeriod::iter() {
+            batch.pipeline.cmd("ZADD").arg(&self.get_id(&period)).arg("GT").arg(new as u64).arg(id).ignore();
+            self.queue_expiry(batch, &period);
+        };
+        batch.commands += 1;
+    }
+
+    // swaps a period's board for the given members in one MULTI. increments that reached the live board after
+    // snapshot_boards_for_rebuild ran are carried over, so nothing written while the rebuild ran is lost
+    pub async fn replace_period(&self, period_id: &str, members: &Vec<(u64, String)>) {
+        let period = unwrap_helper::return_default!(LeaderboardPeriod::from_period_id(period_id), ());
+        let key = format!("{}{}", self.get_key_prefix(), period_id);
+        let staging_key = format!("staging:{}", key);
+        let base_key = format!("{}{}", REBUILD_BASE_KEY_PREFIX, key);
+        let mut batch = LeaderboardBatch::new();
+        batch.pipeline.cmd("DEL").arg(&staging_key).ignore();
+        for chunk in members.chunks(1000) {
+            batch.pipeline.cmd("ZADD").arg(&staging_key).arg(chunk).ignore();
+        };
+        if self.score_type == ScoreType::HighestKillstreak {
+            batch.pipeline.cmd("ZUNIONSTORE").arg(&key).arg(2).arg(&staging_key).arg(&key).arg("AGGREGATE").arg("MAX").ignore();
+        } else {
+            // live - base is whatever was added since the snapshot
+            batch.pipeline.cmd("ZUNIONSTORE").arg(&key).arg(3).arg(&staging_key).arg(&key).arg(&base_key).arg("WEIGHTS").arg(1).arg(1).arg(-1).ignore();
+            batch.pipeline.cmd("ZREMRANGEBYSCORE").arg(&key).arg("-inf").arg(0).ignore();
+        };
+        batch.pipeline.cmd("DEL").arg(&staging_key).arg(&base_key).ignore();
+        if let Some(ttl) = self.scope.get_period_ttl_seconds(&period) {
+            batch.pipeline.cmd("EXPIRE").arg(&key).arg(ttl).ignore();
+        };
+        batch.commands += 1;
+        batch.submit(&self.cache).await;
+    }
+
+    fn queue_expiry(&self, batch: &mut LeaderboardBatch, period: &LeaderboardPeriod) {
+        if let Some(ttl) = self.scope.get_period_ttl_seconds(period) {
+            batch.pipeline.cmd("EXPIRE").arg(&self.get_id(period)).arg(ttl).ignore();
+        };
     }
 
     fn strings_as_leaderboard_entries(raw: Vec<String>) -> Vec<LeaderboardEntry> {
@@ -233,28 +409,71 @@ impl Leaderboard {
     }
 
     pub async fn fetch_top(&self, period: &LeaderboardPeriod, limit: u32) -> Vec<LeaderboardEntry> {
-        let lb_top = self.cache.submit(|mut conn| async move {
-            let top : Option<Vec<String>> = match redis::cmd("ZRANGE").arg(&self.get_id(period)).arg(0u32).arg(limit - 1).arg("REV").arg("WITHSCORES").query_async::<Connection, Vec<String>>(&mut conn).await {
+        self.fetch_top_by_key(&self.get_id(period), limit).await
+    }
+
+    async fn fetch_top_by_key(&self, key: &String, limit: u32) -> Vec<LeaderboardEntry> {
+        if limit == 0 {
+            return Vec::new();
+        };
+        self.fetch_range_by_key(key, 0, (limit - 1) as u64).await
+    }
+
+    // start and stop are zero-based and inclusive, ordered from the highest score
+    async fn fetch_range_by_key(&self, key: &String, start: u64, stop: u64) -> Vec<LeaderboardEntry> {
+        let lb_range = self.cache.submit(|mut conn| async move {
+            let range : Option<Vec<String>> = match redis::cmd("ZRANGE").arg(key).arg(start).arg(stop).arg("REV").arg("WITHSCORES").query_async::<Connection, Vec<String>>(&mut conn).await {
                 Ok(res) => Some(res),
                 Err(_) => None
             };
-            top.unwrap_or(Vec::new())
+            range.unwrap_or(Vec::new())
         }).await.unwrap_or(Vec::new());
-        Self::strings_as_leaderboard_entries(lb_top)
+        Self::strings_as_leaderboard_entries(lb_range)
+    }
+
+    fn as_ranked_entries(entries: Vec<LeaderboardEntry>, start: u64) -> Vec<RankedLeaderboardEntry> {
+        entries.into_iter().enumerate().map(|(index, entry)| {
+            RankedLeaderboardEntry { position: start + index as u64 + 1, entry }
+        }).collect()
+    }
+
+    pub async fn count(&self, period: &LeaderboardPeriod) -> u64 {
+        self.cache.submit(|mut conn| async move {
+            redis::cmd("ZCARD").arg(&self.get_id(period)).query_async::<Connection, u64>(&mut conn).await.unwrap_or(0)
+        }).await.unwrap_or(0)
+    }
+
+    pub async fn fetch_page(&self, period: &LeaderboardPeriod, offset: u64, limit: u32) -> LeaderboardPage {
+        let total = self.count(period).await;
+        if limit == 0 || offset >= total {
+            return LeaderboardPage { entries: Vec::new(), total, next_offset: None };
+        };
+        let entries = self.fetch_range_by_key(&self.get_id(period), offset, offset + limit as u64 - 1).await;
+        let next_offset = offset + entries.len() as u64;
+        LeaderboardPage { 
+            entries: Self::as_ranked_entries(entries, offset), 
+            total, 
+            next_offset: if next_offset < total { Some(next_offset) } else { None } 
+        }
+    }
+
+    // returns up to `radius` entries on either side of the member along with the member itself
+    pub async fn fetch_around(&self, id: &String, period: &LeaderboardPeriod, radius: u32) -> Option<LeaderboardNeighborhood> {
+        let rank = self.get_position(id, period).await?;
+        let total = self.count(period).await;
+        let start = rank.saturating_sub(radius as u64);
+        let entries = self.fetch_range_by_key(&self.get_id(period), start, rank + radius as u64).await;
+        Some(LeaderboardNeighborhood { 
+            position: rank + 1, 
+            total, 
+            entries: Self::as_ranked_entries(entries, start) 
+        })
     }
 
     pub async fn set_if_higher(&self, id: &String, new: u32) {
-        let _ = self.cache.submit(|mut conn| async move {
-            for period in LeaderboardPeriod::iter() {
-                let current = match redis::cmd("ZSCORE").arg(&self.get_id(&period)).arg(id).query_async::<Connection, String>(&mut conn).await {
-                    Ok(res) => { res.parse::<u32>().unwrap() },
-                    Err(_) => { 0u32 }
-                };
-                if new > current {
-                    redis::cmd("ZADD").arg(&self.get_id(&period)).arg(new as f64).arg(id).query_async::<Connection, ()>(&mut conn).await;
-                };
-            };
-        }).await;
+        let mut batch = LeaderboardBatch::new();
+        self.queue_set_if_higher(&mut batch, id, new);
+        batch.submit(&self.cache).await;
     }
 
     pub async fn get_position(&self, id: &String, period: &LeaderboardPeriod) -> Option<u64> {
@@ -267,11 +486,99 @@ impl Leaderboard {
         }).await.unwrap_or(None) // this unwrap occurs if a connection can't be obtained
     }
 
+    // snapshots boards of finished periods into mongo, then lets redis expire them
+    pub async fn archive_finished_periods(&self) {
+        let key_prefix = self.get_key_prefix();
+        let current_period_ids : Vec<String> = LeaderboardPeriod::iter().map(|period| period.get_today_id(&self.periods)).collect();
+        let keys : Vec<String> = self.cache.submit(|mut conn| async move {
+            let mut keys : Vec<String> = Vec::new();
+            if let Ok(mut key_iter) = conn.scan_match::<String, String>(format!("{}*", self.get_key_prefix())).await {
+                while let Some(key) = key_iter.next_item().await {
+                    keys.push(key);
+                };
+            };
+            keys
+        }).await.unwrap_or(Vec::new());
+
+        for key in keys.iter() {
+            let period_id = unwrap_helper::continue_default!(key.strip_prefix(&key_prefix)).to_owned();
+            if current_period_ids.contains(&period_id) {
+                continue;
+            };
+            let period = unwrap_helper::continue_default!(LeaderboardPeriod::from_period_id(&period_id));
+
+            // a board with a ttl has already been archived
+            let ttl = self.cache.submit(|mut conn| async move {
+                redis::cmd("TTL").arg(key).query_async::<Connection, i64>(&mut conn).await.unwrap_or(-2)
+            }).await.unwrap_or(-2);
+            if ttl != -1 {
+                continue;
+            };
+
+            let entries = self.fetch_top_by_key(key, ARCHIVED_LEADERBOARD_SIZE).await.into_iter().enumerate().map(|(index, entry)| {
+                LeaderboardSnapshotEntry { position: (index + 1) as u32, id: entry.id, name: entry.name, score: entry.score }
+            }).collect();
+            self.database.save(&LeaderboardSnapshot { 
+                id: LeaderboardSnapshot::get_snapshot_id(&self.score_type, &period_id), 
+                score_type: self.score_type.clone(), 
+                period, 
+                period_id, 
+                entries, 
+                created_at: get_u64_time_millis() 
+            }).await;
+            let _ = self.cache.submit(|mut conn| async move {
+                let _ = redis::cmd("EXPIRE").arg(key).arg(ARCHIVED_LEADERBOARD_TTL_SECONDS).query_async::<Connection, ()>(&mut conn).await;
+            }).await;
+            info!("Archived leaderboard {}", key);
+        }
+    }
+
+    fn get_key_prefix(&self) -> String {
+        format!("lb:{}{}:", self.scope.get_key_segment(), self.score_type)
+    }
+
     fn get_id(&self, period: &LeaderboardPeriod) -> String {
-        format!("lb:{}:{}", self.score_type, period.get_today_id())
+        format!("{}{}", self.get_key_prefix(), period.get_today_id(&self.periods))
     }
 }
 
+async fn scan_keys(cache: &RedisAdapter, pattern: String) -> Vec<String> {
+    cache.submit(|mut conn| async move {
+        let mut keys : Vec<String> = Vec::new();
+        if let Ok(mut key_iter) = conn.scan_match::<String, String>(pattern).await {
+            while let Some(key) = key_iter.next_item().await {
+                keys.push(key);
+            };
+        };
+        keys
+    }).await.unwrap_or(Vec::new())
+}
+
+// copies every board before a rebuild starts, see Leaderboard::replace_period
+pub async fn snapshot_boards_for_rebuild(cache: &RedisAdapter) {
+    clear_rebuild_snapshots(cache).await;
+    let keys = scan_keys(cache, String::from("lb:*")).await;
+    for chunk in keys.chunks(1000) {
+        let mut batch = LeaderboardBatch::new();
+        for key in chunk.iter() {
+            let base_key = format!("{}{}", REBUILD_BASE_KEY_PREFIX, key);
+            batch.pipeline.cmd("ZUNIONSTORE").arg(&base_key).arg(1).arg(key).ignore();
+            batch.pipeline.cmd("EXPIRE").arg(&base_key).arg(REBUILD_BASE_TTL_SECONDS).ignore();
+            batch.commands += 1;
+        };
+        batch.submit(cache).await;
+    };
+}
+
+pub async fn clear_rebuild_snapshots(cache: &RedisAdapter) {
+    let keys = scan_keys(cache, format!("{}*", REBUILD_BASE_KEY_PREFIX)).await;
+    for chunk in keys.chunks(1000) {
+        let _ = cache.submit(|mut conn| async move {
+            let _ = redis::cmd("DEL").arg(chunk).query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    };
+}
+
 pub struct MarsLeaderboards {
     pub kills: Leaderboard,
     pub deaths: Leaderboard,
@@ -298,38 +605,61 @@ pub struct MarsLeaderboards {
     pub wool_pickups: Leaderboard,
     pub wool_defends: Leaderboard,
     pub control_point_captures: Leaderboard,
-    pub highest_killstreak: Leaderboard
+    pub highest_killstreak: Leaderboard,
+    pub ratios: RatioLeaderboards,
+    pub ratings: RatingLeaderboard,
+    pub periods: LeaderboardPeriodSettings,
+    pub rebuild_status: Mutex<LeaderboardRebuildStatus>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardRebuildStatus {
+    pub running: bool,
+    pub started_at: Option<u64>,
+    // matches that ended before this aren't read, boards of periods starting earlier are left alone
+    pub since: u64,
+    pub finished_at: Option<u64>,
+    pub matches_total: u64,
+    pub matches_processed: u64,
+    // stored before match results were kept, their wins, losses and ties can't be rebuilt
+    pub matches_without_results: u64,
+    pub boards_written: u64
 }
 
 impl MarsLeaderboards {
-    pub fn new(redis: Arc<RedisAdapter>, database: Arc<Database>) -> Self {
+    pub fn new(redis: Arc<RedisAdapter>, database: Arc<Database>, periods: LeaderboardPeriodSettings) -> Self {
         MarsLeaderboards {
-            kills: Leaderboard { score_type: ScoreType::Kills, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            deaths: Leaderboard { score_type: ScoreType::Deaths, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            first_bloods: Leaderboard { score_type: ScoreType::FirstBloods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wins: Leaderboard { score_type: ScoreType::Wins, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            losses: Leaderboard { score_type: ScoreType::Losses, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            ties: Leaderboard { score_type: ScoreType::Ties, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            xp: Leaderboard { score_type: ScoreType::Xp, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            messages_sent: Leaderboard { score_type: ScoreType::MessagesSent, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            matches_played: Leaderboard { score_type: ScoreType::MatchesPlayed, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            server_playtime: Leaderboard { score_type: ScoreType::ServerPlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            game_playtime: Leaderboard { score_type: ScoreType::GamePlaytime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            core_leaks: Leaderboard { score_type: ScoreType::CoreLeaks, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            core_block_destroys: Leaderboard { score_type: ScoreType::CoreBlockDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            destroyable_destroys: Leaderboard { score_type: ScoreType::DestroyableDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            destroyable_block_destroys: Leaderboard { score_type: ScoreType::DestroyableBlockDestroys, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_captures: Leaderboard { score_type: ScoreType::FlagCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_drops: Leaderboard { score_type: ScoreType::FlagDrops, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_pickups: Leaderboard { score_type: ScoreType::FlagPickups, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_defends: Leaderboard { score_type: ScoreType::FlagDefends, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            flag_hold_time: Leaderboard { score_type: ScoreType::FlagHoldTime, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_captures: Leaderboard { score_type: ScoreType::WoolCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_drops: Leaderboard { score_type: ScoreType::WoolDrops, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_pickups: Leaderboard { score_type: ScoreType::WoolPickups, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            wool_defends: Leaderboard { score_type: ScoreType::WoolDefends, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            control_point_captures: Leaderboard { score_type: ScoreType::ControlPointCaptures, cache: Arc::clone(&redis), database: Arc::clone(&database) },
-            highest_killstreak: Leaderboard { score_type: ScoreType::HighestKillstreak, cache: Arc::clone(&redis), database: Arc::clone(&database) }
+            kills: Leaderboard { score_type: ScoreType::Kills, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            deaths: Leaderboard { score_type: ScoreType::Deaths, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            first_bloods: Leaderboard { score_type: ScoreType::FirstBloods, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wins: Leaderboard { score_type: ScoreType::Wins, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            losses: Leaderboard { score_type: ScoreType::Losses, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            ties: Leaderboard { score_type: ScoreType::Ties, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            xp: Leaderboard { score_type: ScoreType::Xp, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            messages_sent: Leaderboard { score_type: ScoreType::MessagesSent, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            matches_played: Leaderboard { score_type: ScoreType::MatchesPlayed, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            server_playtime: Leaderboard { score_type: ScoreType::ServerPlaytime, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            game_playtime: Leaderboard { score_type: ScoreType::GamePlaytime, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            core_leaks: Leaderboard { score_type: ScoreType::CoreLeaks, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            core_block_destroys: Leaderboard { score_type: ScoreType::CoreBlockDestroys, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            destroyable_destroys: Leaderboard { score_type: ScoreType::DestroyableDestroys, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            destroyable_block_destroys: Leaderboard { score_type: ScoreType::DestroyableBlockDestroys, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_captures: Leaderboard { score_type: ScoreType::FlagCaptures, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_drops: Leaderboard { score_type: ScoreType::FlagDrops, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_pickups: Leaderboard { score_type: ScoreType::FlagPickups, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_defends: Leaderboard { score_type: ScoreType::FlagDefends, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            flag_hold_time: Leaderboard { score_type: ScoreType::FlagHoldTime, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_captures: Leaderboard { score_type: ScoreType::WoolCaptures, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_drops: Leaderboard { score_type: ScoreType::WoolDrops, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_pickups: Leaderboard { score_type: ScoreType::WoolPickups, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            wool_defends: Leaderboard { score_type: ScoreType::WoolDefends, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            control_point_captures: Leaderboard { score_type: ScoreType::ControlPointCaptures, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            highest_killstreak: Leaderboard { score_type: ScoreType::HighestKillstreak, scope: LeaderboardScope::Global, periods, cache: Arc::clone(&redis), database: Arc::clone(&database) },
+            ratios: RatioLeaderboards::new(Arc::clone(&redis), Arc::clone(&database)),
+            ratings: RatingLeaderboard { cache: Arc::clone(&redis) },
+            periods,
+            rebuild_status: Mutex::new(LeaderboardRebuildStatus::default())
         }
     }
 
@@ -372,3 +702,98 @@ pub struct LeaderboardEntry {
     pub name: String,
     pub score: u32
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedLeaderboardEntry {
+    pub position: u64,
+    #[serde(flatten)]
+    pub entry: LeaderboardEntry
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardPage {
+    pub entries: Vec<RankedLeaderboardEntry>,
+    pub total: u64,
+    pub next_offset: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardNeighborhood {
+    pub position: u64,
+    pub total: u64,
+    pub entries: Vec<RankedLeaderboardEntry>
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use super::{LeaderboardPeriod, LeaderboardPeriodSettings};
+
+    fn settings(week_start: Weekday, legacy_ids: bool) -> LeaderboardPeriodSettings {
+        LeaderboardPeriodSettings { timezone: Tz::America__New_York, week_start, legacy_ids }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn legacy_ids_use_zero_based_months() {
+        let date = date(2026, 3, 14);
+        let legacy = settings(Weekday::Mon, true);
+        let current = settings(Weekday::Mon, false);
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&date, &legacy), "2026:d:2:14");
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&date, &current), "2026:d:3:14");
+        assert_eq!(LeaderboardPeriod::Monthly.get_id_for_date(&date, &legacy), "2026:m:2");
+        assert_eq!(LeaderboardPeriod::Monthly.get_id_for_date(&date, &current), "2026:m:3");
+        assert_eq!(LeaderboardPeriod::Seasonally.get_id_for_date(&date, &current), "2026:s:spring");
+        assert_eq!(LeaderboardPeriod::Yearly.get_id_for_date(&date, &current), "2026:y");
+        assert_eq!(LeaderboardPeriod::AllTime.get_id_for_date(&date, &current), "all");
+    }
+
+    #[test]
+    fn week_start_shifts_week_boundary() {
+        let monday = settings(Weekday::Mon, false);
+        let sunday = settings(Weekday::Sun, false);
+        // 2026-03-15 is a sunday
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2026, 3, 14), &monday), "2026:w:11");
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2026, 3, 15), &monday), "2026:w:11");
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2026, 3, 14), &sunday), "2026:w:11");
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2026, 3, 15), &sunday), "2026:w:12");
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2026, 3, 16), &sunday), "2026:w:12");
+    }
+
+    #[test]
+    fn week_ids_roll_over_with_iso_year() {
+        let legacy = settings(Weekday::Mon, true);
+        let current = settings(Weekday::Mon, false);
+        // 2026-12-31 and 2027-01-01 both fall in ISO week 53 of 2026
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2026, 12, 31), &current), "2026:w:53");
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2027, 1, 1), &current), "2026:w:53");
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2027, 1, 4), &current), "2027:w:1");
+        // legacy ids split the week across calendar years
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2027, 1, 1), &legacy), "2027:w:53");
+        // with a sunday start, 2027-01-03 already belongs to the first week of 2027
+        assert_eq!(LeaderboardPeriod::Weekly.get_id_for_date(&date(2027, 1, 3), &settings(Weekday::Sun, false)), "2027:w:1");
+    }
+
+    #[test]
+    fn dates_follow_local_time_across_dst() {
+        let current = settings(Weekday::Mon, false);
+        let millis = |year, month, day, hour, minute| Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap().timestamp_millis() as u64;
+        // spring forward on 2026-03-08, local midnight is still 05:00 UTC
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 3, 8, 4, 59)), &current), "2026:d:3:7");
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 3, 8, 5, 0)), &current), "2026:d:3:8");
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 3, 9, 3, 59)), &current), "2026:d:3:8");
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 3, 9, 4, 0)), &current), "2026:d:3:9");
+        // fall back on 2026-11-01, the day is 25 hours long
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 11, 1, 4, 0)), &current), "2026:d:11:1");
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 11, 2, 4, 59)), &current), "2026:d:11:1");
+        assert_eq!(LeaderboardPeriod::Daily.get_id_for_date(&current.date_of(millis(2026, 11, 2, 5, 0)), &current), "2026:d:11:2");
+    }
+}
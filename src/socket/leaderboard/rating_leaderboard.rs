@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use redis::aio::Connection;
+
+use crate::database::{cache::RedisAdapter, models::level::LevelGamemode};
+
+use super::{Leaderboard, LeaderboardEntry};
+
+// one board per gamemode, holding each player's current rating rather than a per-period counter
+pub struct RatingLeaderboard {
+    pub cache: Arc<RedisAdapter>
+}
+
+impl RatingLeaderboard {
+    pub async fn set(&self, gamemode: &LevelGamemode, id: &String, rating: f64) {
+        let key = Self::get_id(gamemode);
+        let _ = self.cache.submit(|mut conn| async move {
+            let _ = redis::cmd("ZADD").arg(&key).arg(rating.round() as i64).arg(id).query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+
+    pub async fn fetch_top(&self, gamemode: &LevelGamemode, limit: u32) -> Vec<LeaderboardEntry> {
+        if limit == 0 {
+            return Vec::new();
+        };
+        let key = Self::get_id(gamemode);
+        let raw = self.cache.submit(|mut conn| async move {
+            redis::cmd("ZRANGE").arg(&key).arg(0u32).arg(limit - 1).arg("REV").arg("WITHSCORES")
+                .query_async::<Connection, Vec<String>>(&mut conn).await.unwrap_or(Vec::new())
+        }).await.unwrap_or(Vec::new());
+        Leaderboard::strings_as_leaderboard_entries(raw)
+    }
+
+    fn get_id(gamemode: &LevelGamemode) -> String {
+        format!("lb:rating:{}", gamemode)
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use mongodb::{bson::doc, options::FindOptions};
+use redis::aio::Connection;
+use strum::IntoEnumIterator;
+use serde::{Serialize, Deserialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+use crate::{config::MarsConfigOptions, database::{cache::RedisAdapter, Database, }, util::r#macro::unwrap_helper};
+
+const MILLIS_PER_HOUR : f64 = 3_600_000.0;
+
+// the only stats the ratios are computed from, loaded with a projection
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RatioStats {
+    #[serde(default)]
+    xp: u32,
+    #[serde(default)]
+    game_playtime: u64,
+    #[serde(default)]
+    kills: u32,
+    #[serde(default)]
+    deaths: u32,
+    #[serde(default)]
+    bow_shots_taken: u32,
+    #[serde(default)]
+    bow_shots_hit: u32,
+    #[serde(default)]
+    wins: u32,
+    #[serde(default)]
+    matches: u32
+}
+
+#[derive(Deserialize)]
+struct RatioPlayerDocument {
+    #[serde(rename = "_id")]
+    id: String,
+    name: String,
+    stats: RatioStats
+}
+
+#[derive(Debug, Display, EnumString, EnumIter, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum RatioType {
+    KillDeathRatio,
+    WinRate,
+    BowAccuracy,
+    XpPerHour
+}
+
+impl RatioType {
+    pub fn to_leaderboard<'a>(&self, lbs: &'a RatioLeaderboards) -> &'a RatioLeaderboard {
+        match self {
+            RatioType::KillDeathRatio => &lbs.kill_death_ratio,
+            RatioType::WinRate => &lbs.win_rate,
+            RatioType::BowAccuracy => &lbs.bow_accuracy,
+            RatioType::XpPerHour => &lbs.xp_per_hour
+        }
+    }
+
+    // the amount a player has to reach before their ratio means anything
+    fn get_sample(&self, stats: &RatioStats) -> u64 {
+        match self {
+            RatioType::KillDeathRatio | RatioType::WinRate => stats.matches as u64,
+            RatioType::BowAccuracy => stats.bow_shots_taken as u64,
+            RatioType::XpPerHour => stats.game_playtime / MILLIS_PER_HOUR as u64
+        }
+    }
+
+    fn get_minimum_sample(&self, options: &MarsConfigOptions) -> u64 {
+        match self {
+            RatioType::KillDeathRatio | RatioType::WinRate => options.ratio_min_matches,
+            RatioType::BowAccuracy => options.ratio_min_bow_shots,
+            RatioType::XpPerHour => options.ratio_min_playtime_hours
+        }
+    }
+
+    fn compute(&self, stats: &RatioStats) -> Option<f64> {
+        match self {
+            RatioType::KillDeathRatio => Some(stats.kills as f64 / stats.deaths.max(1) as f64),
+            RatioType::WinRate => if stats.matches == 0 { None } else { Some(stats.wins as f64 / stats.matches as f64) },
+            RatioType::BowAccuracy => if stats.bow_shots_taken == 0 { None } else { Some(stats.bow_shots_hit as f64 / stats.bow_shots_taken as f64) },
+            RatioType::XpPerHour => if stats.game_playtime == 0 { None } else { Some(stats.xp as f64 / (stats.game_playtime as f64 / MILLIS_PER_HOUR)) }
+        }
+    }
+}
+
+pub struct RatioLeaderboard {
+    pub ratio_type: RatioType,
+    pub cache: Arc<RedisAdapter>
+}
+
+impl RatioLeaderboard {
+    // built aside and swapped in within one MULTI so readers never see a partial board
+    async fn replace(&self, members: Vec<(f64, String)>) {
+        let key = self.get_id();
+        let staging_key = format!("staging:{}", key);
+        let _ = self.cache.submit(|mut conn| async move {
+            let mut pipeline = redis::pipe();
+            pipeline.atomic().cmd("DEL").arg(&staging_key).ignore();
+            if members.is_empty() {
+                pipeline.cmd("DEL").arg(&key).ignore();
+            } else {
+                for chunk in members.chunks(1000) {
+                    pipeline.cmd("ZADD").arg(&staging_key).arg(chunk).ignore();
+                };
+                pipeline.cmd("RENAME").arg(&staging_key).arg(&key).ignore();
+            };
+            let _ = pipeline.query_async::<Connection, ()>(&mut conn).await;
+        }).await;
+    }
+
+    pub async fn fetch_top(&self, limit: u32) -> Vec<RatioLeaderboardEntry> {
+        if limit == 0 {
+            return Vec::new();
+        };
+        let key = self.get_id();
+        let raw = self.cache.submit(|mut conn| async move {
+            redis::cmd("ZRANGE").arg(&key).arg(0u32).arg(limit - 1).arg("REV").arg("WITHSCORES")
+                .query_async::<Connection, Vec<String>>(&mut conn).await.unwrap_or(Vec::new())
+        }).await.unwrap_or(Vec::new());
+        raw.chunks_exact(2).filter_map(|pair| {
+            let (id, name) = pair[0].split_once("/")?;
+            Some(RatioLeaderboardEntry { id: id.to_owned(), name: name.to_owned(), score: pair[1].parse::<f64>().unwrap_or(0.0) })
+        }).collect()
+    }
+
+    fn get_id(&self) -> String {
+        format!("lb:ratio:{}:all", self.ratio_type)
+    }
+}
+
+pub struct RatioLeaderboards {
+    pub kill_death_ratio: RatioLeaderboard,
+    pub win_rate: RatioLeaderboard,
+    pub bow_accuracy: RatioLeaderboard,
+    pub xp_per_hour: RatioLeaderboard,
+    pub database: Arc<Database>
+}
+
+impl RatioLeaderboards {
+    pub fn new(redis: Arc<RedisAdapter>, database: Arc<Database>) -> Self {
+        RatioLeaderboards {
+            kill_death_ratio: RatioLeaderboard { ratio_type: RatioType::KillDeathRatio, cache: Arc::clone(&redis) },
+            win_rate: RatioLeaderboard { ratio_type: RatioType::WinRate, cache: Arc::clone(&redis) },
+            bow_accuracy: RatioLeaderboard { ratio_type: RatioType::BowAccuracy, cache: Arc::clone(&redis) },
+            xp_per_hour: RatioLeaderboard { ratio_type: RatioType::XpPerHour, cache: Arc::clone(&redis) },
+            database
+        }
+    }
+
+    // ratios can't be kept up incrementally from counters, so every board is rebuilt from one pass over player stats
+    pub async fn recompute(&self, options: &MarsConfigOptions) {
+        let opts = FindOptions::builder().projection(doc! {
+            "_id": 1, "name": 1, "stats.xp": 1, "stats.gamePlaytime": 1, "stats.kills": 1, "stats.deaths": 1,
+            "stats.bowShotsTaken": 1, "stats.bowShotsHit": 1, "stats.wins": 1, "stats.matches": 1
+        }).build();
+        let cursor = unwrap_helper::result_return_default!(self.database.players.clone_with_type::<RatioPlayerDocument>().find(doc! {}, opts).await, ());
+        let players = Database::consume_cursor_into_owning_vec(cursor).await;
+        for ratio_type in RatioType::iter() {
+            let minimum_sample = ratio_type.get_minimum_sample(options);
+            let members : Vec<(f64, String)> = players.iter().filter_map(|player| {
+                if ratio_type.get_sample(&player.stats) < minimum_sample {
+                    return None;
+                };
+                let ratio = ratio_type.compute(&player.stats)?;
+                Some((ratio, format!("{}/{}", player.id, player.name)))
+            }).collect();
+            ratio_type.to_leaderboard(self).replace(members).await;
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatioLeaderboardEntry {
+    pub id: String,
+    pub name: String,
+    pub score: f64
+}
@@ -0,0 +1,43 @@
+use mongodb::bson::doc;
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+
+use crate::{database::{Database, models::{r#match::Match, level_record::{LevelRecordType, LevelRecordEntry}}}, socket::server::server_context::ServerContext, util::{time::get_u64_time_millis, r#macro::unwrap_helper}};
+
+pub struct MapRecordHistoryListener {}
+
+impl MapRecordHistoryListener {
+    // must run before the match's level is saved, the stored level holds the records from before this match
+    pub async fn on_match_end(server_context: &ServerContext, current_match: &Match) {
+        let database = &server_context.api_state.database;
+        let stored_level = unwrap_helper::return_default!(Database::find_by_id(&database.levels, &current_match.level.id).await, ());
+        let now = get_u64_time_millis();
+        for record_type in LevelRecordType::iter() {
+            let (match_id, holder, value) = unwrap_helper::continue_default!(record_type.get_current(&current_match.level.records));
+            if match_id != current_match.id {
+                continue;
+            };
+            if let Some((_, previous_holder, previous_value)) = record_type.get_current(&stored_level.records) {
+                if previous_holder.id == holder.id && previous_value == value {
+                    continue;
+                };
+            };
+
+            let _ = database.level_records.update_many(
+                doc! { "levelId": &current_match.level.id, "recordType": record_type.to_string(), "brokenAt": null },
+                doc! { "$set": { "brokenAt": now as i64 } },
+                None
+            ).await;
+            database.insert_one(&LevelRecordEntry {
+                id: Uuid::new_v4().to_string(),
+                record_type,
+                level_id: current_match.level.id.clone(),
+                holder,
+                value,
+                match_id,
+                achieved_at: now,
+                broken_at: None
+            }).await;
+        };
+    }
+}
@@ -0,0 +1,45 @@
+use mongodb::{bson::{doc, Document}, options::UpdateOptions};
+
+use crate::{database::models::{r#match::Match, level_stats::RECENT_DURATION_COUNT}, socket::{r#match::match_events::MatchEndData, server::server_context::ServerContext}, util::time::get_u64_time_millis};
+
+pub struct MapStatsListener {}
+
+impl MapStatsListener {
+    // party names end up as document keys
+    fn get_party_key(party_name: &String) -> String {
+        party_name.replace(".", "_").replace("$", "_")
+    }
+
+    pub async fn on_match_end(server_context: &ServerContext, current_match: &Match, end_data: &MatchEndData) {
+        let duration = current_match.get_length();
+        let participants = current_match.participants.values().filter(|participant| participant.last_party_name.is_some()).count() as i64;
+        let kills : i64 = current_match.participants.values().map(|participant| participant.stats.kills as i64).sum();
+        let is_tie = end_data.is_tie(current_match);
+
+        let mut increments = doc! {
+            "timesPlayed": 1,
+            "totalDuration": duration as i64,
+            "totalParticipants": participants,
+            "totalKills": kills,
+            "ties": if is_tie { 1 } else { 0 }
+        };
+        for party_name in current_match.parties.keys() {
+            let party_key = Self::get_party_key(party_name);
+            increments.insert(format!("partyResults.{}.matches", party_key), 1);
+            increments.insert(format!("partyResults.{}.wins", party_key), if !is_tie && end_data.winning_parties.contains(party_name) { 1 } else { 0 });
+        };
+        for gamemode in current_match.level.gamemodes.iter() {
+            increments.insert(format!("gamemodePlays.{}", gamemode), 1);
+        };
+
+        let update : Document = doc! {
+            "$inc": increments,
+            "$push": { "recentDurations": { "$each": [duration as i64], "$slice": -RECENT_DURATION_COUNT } },
+            "$set": { "lastPlayedAt": get_u64_time_millis() as i64 }
+        };
+        let update_opts = UpdateOptions::builder().upsert(Some(true)).build();
+        if let Err(e) = server_context.api_state.database.level_stats.update_one(doc! { "_id": &current_match.level.id }, update, Some(update_opts)).await {
+            warn!("Could not update stats for map {}: {}", current_match.level.id, e);
+        };
+    }
+}
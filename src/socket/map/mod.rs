@@ -1 +1,3 @@
-pub mod map_record_listener;
\ No newline at end of file
+pub mod map_record_listener;
+pub mod map_stats_listener;
+pub mod map_record_history_listener;
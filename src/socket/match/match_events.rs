@@ -2,7 +2,7 @@ use std::collections::{HashSet, HashMap};
 
 use serde::{Serialize, Deserialize};
 
-use crate::database::models::{participant::SimpleParticipant, r#match::Match};
+use crate::database::models::{participant::SimpleParticipant, r#match::Match, rating::RatingChange};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,7 +14,10 @@ pub struct MatchStartData {
 #[serde(rename_all = "camelCase")]
 pub struct MatchEndData {
     pub winning_parties: Vec<String>,
-    pub big_stats: HashMap<String, BigStats>
+    pub big_stats: HashMap<String, BigStats>,
+    // filled in by the api before players are processed, keyed by player id
+    #[serde(skip)]
+    pub rating_changes: HashMap<String, Vec<RatingChange>>
 }
 
 impl MatchEndData {
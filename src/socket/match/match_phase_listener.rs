@@ -32,7 +32,8 @@ impl MatchPhaseListener<'_> {
             parties,
             participants: HashMap::new(),
             server_id: self.server.id.clone(),
-            first_blood: None
+            first_blood: None,
+            winning_parties: None
         };
 
 
@@ -56,11 +57,12 @@ impl MatchPhaseListener<'_> {
         Ok(current_match)
     }
 
-    pub fn on_end(&self, _data: &MatchEndData, mut current_match: Match) -> Result<Match, SocketError> {
+    pub fn on_end(&self, data: &MatchEndData, mut current_match: Match) -> Result<Match, SocketError> {
         if MatchState::InProgress != current_match.get_state() {
             return Err(SocketError::InvalidMatchState)
         };
         current_match.ended_at = Some(get_u64_time_millis());
+        current_match.winning_parties = Some(data.winning_parties.clone());
         info!("({}) Match ended: {}", self.server.id, current_match.id);
         Ok(current_match)
     }
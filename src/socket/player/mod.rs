@@ -5,3 +5,4 @@ pub mod player_stat_listener;
 pub mod player_gamemode_stat_listener;
 pub mod player_xp_listener;
 pub mod player_record_listener;
+pub mod player_rating_listener;
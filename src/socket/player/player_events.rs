@@ -84,6 +84,16 @@ pub struct PlayerXPGainData {
     pub notify: bool
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerLevelUpData {
+    pub player_id: String,
+    pub level: u32,
+    pub tag_ids: Vec<String>,
+    pub join_sound_ids: Vec<String>,
+    pub rank_ids: Vec<String>
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DisconnectPlayerData {
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::{MarsAPIState, database::models::{player::{Player, SimplePlayer}, rating::{RatingChange, SkillRating, DEFAULT_RATING}, level::LevelGamemode, r#match::Match}, socket::{server::server_context::ServerContext, r#match::match_events::MatchEndData, participant::participant_context::PlayerMatchResult}, util::{r#macro::unwrap_helper, time::get_u64_time_millis}};
+
+use super::player_listener::PlayerListener;
+
+const PROVISIONAL_MATCHES : u32 = 10;
+const PROVISIONAL_K_FACTOR : f64 = 40.0;
+const K_FACTOR : f64 = 24.0;
+
+pub struct PlayerRatingListener {}
+
+impl PlayerRatingListener {
+    fn get_expected_score(rating: f64, opponent_rating: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+    }
+
+    // elo against the average of the opposing parties, moving faster while the rating is provisional
+    fn get_new_rating(current: &SkillRating, party_rating: f64, opponent_ratings: &Vec<f64>, result: &PlayerMatchResult) -> f64 {
+        let expected = opponent_ratings.iter().map(|opponent_rating| Self::get_expected_score(party_rating, *opponent_rating)).sum::<f64>() / opponent_ratings.len() as f64;
+        let actual = match result {
+            PlayerMatchResult::Win => 1.0,
+            PlayerMatchResult::Tie => 0.5,
+            _ => 0.0
+        };
+        let k_factor = if current.matches < PROVISIONAL_MATCHES { PROVISIONAL_K_FACTOR } else { K_FACTOR };
+        current.rating + k_factor * (actual - expected)
+    }
+
+    // every change depends on the average rating of each party, so the whole match is settled before players are processed
+    pub async fn compute_rating_changes(state: &MarsAPIState, current_match: &Match, end_data: &MatchEndData) -> HashMap<String, Vec<RatingChange>> {
+        let mut changes : HashMap<String, Vec<RatingChange>> = HashMap::new();
+        if !current_match.is_tracking_stats() {
+            return changes;
+        };
+
+        let mut players : Vec<(SimplePlayer, String, PlayerMatchResult, HashMap<LevelGamemode, SkillRating>)> = Vec::new();
+        for participant in current_match.participants.values() {
+            // leaving before the end counts as a loss for the party the player was on, so quitting can't dodge one
+            let (party_name, result) = match &participant.party_name {
+                Some(party_name) => (party_name.clone(), current_match.get_participant_match_result(participant, end_data)),
+                None => (unwrap_helper::continue_default!(participant.last_party_name.clone()), PlayerMatchResult::Lose)
+            };
+            let player = unwrap_helper::continue_default!(state.player_cache.get(&state.database, &participant.get_name_lower()).await);
+            players.push((player.to_simple(), party_name, result, player.ratings));
+        };
+
+        let time_millis = get_u64_time_millis();
+        for gamemode in current_match.level.gamemodes.iter() {
+            let mut party_totals : HashMap<&String, (f64, u32)> = HashMap::new();
+            for (_, party_name, _, ratings) in players.iter() {
+                let total = party_totals.entry(party_name).or_insert((0.0, 0));
+                total.0 += ratings.get(gamemode).map(|rating| rating.rating).unwrap_or(DEFAULT_RATING);
+                total.1 += 1;
+            };
+            // nothing to be rated against
+            if party_totals.len() < 2 {
+                continue;
+            };
+            let party_ratings : HashMap<&String, f64> = party_totals.into_iter().map(|(party_name, (sum, count))| (party_name, sum / count as f64)).collect();
+
+            for (player, party_name, result, ratings) in players.iter() {
+                let party_rating = party_ratings[party_name];
+                let opponent_ratings : Vec<f64> = party_ratings.iter().filter(|(other, _)| *other != &party_name).map(|(_, rating)| *rating).collect();
+                let current = ratings.get(gamemode).cloned().unwrap_or_default();
+                changes.entry(player.id.clone()).or_insert(Vec::new()).push(RatingChange { 
+                    id: Uuid::new_v4().to_string(), 
+                    player: player.clone(), 
+                    gamemode: gamemode.clone(), 
+                    match_id: current_match.id.clone(), 
+                    result: result.clone(), 
+                    previous_rating: current.rating, 
+                    rating: Self::get_new_rating(&current, party_rating, &opponent_ratings, result), 
+                    created_at: time_millis 
+                });
+            };
+        };
+        changes
+    }
+}
+
+#[async_trait]
+impl PlayerListener for PlayerRatingListener {
+    type Context = Player;
+
+    async fn on_match_end_v2(
+        &self,
+        server_context: &mut ServerContext, 
+        _current_match: &mut Match, 
+        context: &mut Self::Context, 
+        end_data: &mut MatchEndData
+    ) { 
+        let changes = unwrap_helper::return_default!(end_data.rating_changes.remove(&context.id), ());
+        for change in changes.iter() {
+            let rating = context.ratings.entry(change.gamemode.clone()).or_insert(SkillRating::default());
+            rating.rating = change.rating;
+            rating.peak_rating = rating.peak_rating.max(change.rating);
+            rating.matches += 1;
+            rating.updated_at = change.created_at;
+            server_context.api_state.leaderboards.ratings.set(&change.gamemode, &context.id_name(), change.rating).await;
+        };
+        if !changes.is_empty() {
+            let _ = server_context.api_state.database.rating_changes.insert_many(&changes, None).await;
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::models::rating::{SkillRating, DEFAULT_RATING}, socket::participant::participant_context::PlayerMatchResult};
+
+    use super::{PlayerRatingListener, K_FACTOR, PROVISIONAL_K_FACTOR, PROVISIONAL_MATCHES};
+
+    fn rating(rating: f64, matches: u32) -> SkillRating {
+        SkillRating { rating, peak_rating: rating, matches, updated_at: 0 }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn expected_score_is_symmetric() {
+        assert_close(PlayerRatingListener::get_expected_score(1500.0, 1500.0), 0.5);
+        let favoured = PlayerRatingListener::get_expected_score(1900.0, 1500.0);
+        assert_close(favoured, 10.0 / 11.0);
+        assert_close(favoured + PlayerRatingListener::get_expected_score(1500.0, 1900.0), 1.0);
+    }
+
+    #[test]
+    fn even_match_moves_by_half_the_k_factor() {
+        let current = rating(DEFAULT_RATING, PROVISIONAL_MATCHES);
+        let opponents = vec![DEFAULT_RATING];
+        assert_close(PlayerRatingListener::get_new_rating(&current, DEFAULT_RATING, &opponents, &PlayerMatchResult::Win), DEFAULT_RATING + K_FACTOR / 2.0);
+        assert_close(PlayerRatingListener::get_new_rating(&current, DEFAULT_RATING, &opponents, &PlayerMatchResult::Lose), DEFAULT_RATING - K_FACTOR / 2.0);
+        assert_close(PlayerRatingListener::get_new_rating(&current, DEFAULT_RATING, &opponents, &PlayerMatchResult::Tie), DEFAULT_RATING);
+    }
+
+    #[test]
+    fn provisional_ratings_use_the_larger_k_factor() {
+        let current = rating(DEFAULT_RATING, PROVISIONAL_MATCHES - 1);
+        let new_rating = PlayerRatingListener::get_new_rating(&current, DEFAULT_RATING, &vec![DEFAULT_RATING], &PlayerMatchResult::Win);
+        assert_close(new_rating, DEFAULT_RATING + PROVISIONAL_K_FACTOR / 2.0);
+    }
+
+    #[test]
+    fn change_uses_the_party_rating_against_every_opponent() {
+        // the player's own rating only shifts the result, the expectation comes from the party averages
+        let current = rating(1700.0, PROVISIONAL_MATCHES);
+        let opponents = vec![1900.0, 1100.0];
+        let expected = (PlayerRatingListener::get_expected_score(1500.0, 1900.0) + PlayerRatingListener::get_expected_score(1500.0, 1100.0)) / 2.0;
+        let new_rating = PlayerRatingListener::get_new_rating(&current, 1500.0, &opponents, &PlayerMatchResult::Win);
+        assert_close(new_rating, 1700.0 + K_FACTOR * (1.0 - expected));
+        let upset = PlayerRatingListener::get_new_rating(&current, 1500.0, &vec![1900.0], &PlayerMatchResult::Win);
+        let expected_win = PlayerRatingListener::get_new_rating(&current, 1500.0, &vec![1100.0], &PlayerMatchResult::Win);
+        assert!(upset - 1700.0 > expected_win - 1700.0);
+    }
+}
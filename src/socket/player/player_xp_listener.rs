@@ -1,6 +1,6 @@
-use crate::{socket::{server::server_context::ServerContext, r#match::match_events::MatchEndData, participant::participant_context::PlayerMatchResult}, database::models::{player::Player, r#match::{Match, DestroyableGoal}}};
+use crate::{socket::{server::server_context::ServerContext, r#match::match_events::MatchEndData, participant::participant_context::PlayerMatchResult, event_type::EventType}, database::models::{player::{Player, LevelUpRecord}, r#match::{Match, DestroyableGoal}, tag::Tag, rank::{Rank, RankGrant}}, util::{time::get_u64_time_millis, r#macro::unwrap_helper}};
 
-use super::{player_listener::PlayerListener, player_events::PlayerDeathData};
+use super::{player_listener::PlayerListener, player_events::{PlayerDeathData, PlayerLevelUpData}, player_context::send_message_to_player};
 
 pub struct PlayerXPListener {}
 
@@ -25,6 +25,58 @@ impl PlayerXPListener {
         let start_multiplier = u32::max(XP_BEGINNER_ASSIST_MAX - level, 1);
         xp * start_multiplier
     }
+
+    pub async fn on_level_up(server_context: &mut ServerContext, player: &mut Player, previous_level: u32, level: u32) {
+        let api_state = server_context.api_state.clone();
+        let time_millis = get_u64_time_millis();
+        for reached_level in (previous_level + 1)..=level {
+            player.level_ups.push(LevelUpRecord { level: reached_level, reached_at: time_millis });
+            let mut level_up_data = PlayerLevelUpData { 
+                player_id: player.id.clone(), 
+                level: reached_level, 
+                tag_ids: Vec::new(), 
+                join_sound_ids: Vec::new(), 
+                rank_ids: Vec::new() 
+            };
+
+            for reward in api_state.config.data.level_rewards.iter().filter(|reward| reward.level == reached_level) {
+                for tag_id in reward.tags.iter() {
+                    let tag = unwrap_helper::continue_default!(api_state.database.find_by_id_or_name::<Tag>(tag_id).await);
+                    if !player.tag_ids.contains(&tag.id) {
+                        player.tag_ids.push(tag.id.clone());
+                        level_up_data.tag_ids.push(tag.id);
+                    };
+                }
+                for join_sound_id in reward.join_sounds.iter() {
+                    let is_known_sound = api_state.config.data.join_sounds.iter().any(|sound| &sound.id == join_sound_id);
+                    if is_known_sound && !player.join_sound_ids.contains(join_sound_id) {
+                        player.join_sound_ids.push(join_sound_id.clone());
+                        level_up_data.join_sound_ids.push(join_sound_id.clone());
+                    };
+                }
+                for rank_id in reward.ranks.iter() {
+                    let rank = unwrap_helper::continue_default!(api_state.database.find_by_id_or_name::<Rank>(rank_id).await);
+                    if !player.rank_ids.contains(&rank.id) {
+                        player.set_rank_grant(RankGrant { 
+                            rank_id: rank.id.clone(), 
+                            granted_at: time_millis, 
+                            expires_at: None, 
+                            reason: Some(format!("Level {} reward", reached_level)), 
+                            granter: None 
+                        });
+                        level_up_data.rank_ids.push(rank.id);
+                    };
+                }
+            }
+
+            info!("{} reached level {}", player.id_name(), reached_level);
+            server_context.call(&EventType::PlayerLevelUp, level_up_data).await;
+        }
+
+        for (rank, promotion) in player.apply_rank_promotions(&api_state).await {
+            send_message_to_player(server_context, player, &promotion.get_message(&rank), None).await;
+        }
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use rocket::serde::json::{serde_json, Value};
+use serde::Serialize;
+use tokio::sync::{Mutex, mpsc::{UnboundedSender, UnboundedReceiver, unbounded_channel}};
+use uuid::Uuid;
+
+use crate::socket::event_type::EventType;
+
+pub struct OutboundEvent {
+    pub event: EventType,
+    pub data: Value
+}
+
+struct OutboxConnection {
+    connection_id: String,
+    sender: UnboundedSender<OutboundEvent>
+}
+
+// lets http handlers reach servers connected over the socket, only servers connected to this instance are reachable
+pub struct ServerOutbox {
+    connections: Mutex<HashMap<String, OutboxConnection>>
+}
+
+impl ServerOutbox {
+    pub fn new() -> Self {
+        ServerOutbox { connections: Mutex::new(HashMap::new()) }
+    }
+
+    // a reconnecting server replaces its previous connection
+    pub async fn register(&self, server_id: &str) -> (String, UnboundedReceiver<OutboundEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        let connection_id = Uuid::new_v4().to_string();
+        self.connections.lock().await.insert(server_id.to_owned(), OutboxConnection { connection_id: connection_id.clone(), sender });
+        (connection_id, receiver)
+    }
+
+    pub async fn unregister(&self, server_id: &str, connection_id: &str) {
+        let mut connections = self.connections.lock().await;
+        if connections.get(server_id).map_or(false, |connection| connection.connection_id == connection_id) {
+            connections.remove(server_id);
+        };
+    }
+
+    // false when the server isn't connected
+    pub async fn send<T: Serialize>(&self, server_id: &str, event: EventType, data: T) -> bool {
+        let data = match serde_json::to_value(data) {
+            Ok(data) => data,
+            Err(_) => return false
+        };
+        match self.connections.lock().await.get(server_id) {
+            Some(connection) => connection.sender.send(OutboundEvent { event, data }).is_ok(),
+            None => false
+        }
+    }
+}
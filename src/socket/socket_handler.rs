@@ -18,6 +18,7 @@ use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 
 use crate::MarsAPIState;
+use crate::database::models::presence::{PlayerPresence, SERVER_PRESENCE_REFRESH_MS};
 use crate::socket::event_type::EventType;
 use crate::socket::socket_router::SocketRouter;
 use crate::util::error::ApiErrorResponder;
@@ -90,9 +91,22 @@ async fn accept_connection(
     };
     
     let mut router = SocketRouter::new(server);
+    let (connection_id, mut outbound) = socket_session.api_state.server_outbox.register(&server_id).await;
+    let mut presence_refreshed_at : u64 = 0;
 
-    while let Some(msg) = router.server.stream.next().await {
-        let msg = unwrap_helper::continue_default!(msg.ok());
+    loop {
+        let msg = tokio::select! {
+            msg = router.server.stream.next() => msg,
+            Some(outbound_event) = outbound.recv() => {
+                info!("[{}:{}] -> {}", server_id, outbound_event.event, outbound_event.data.to_string());
+                router.server.call(&outbound_event.event, outbound_event.data).await;
+                continue;
+            }
+        };
+        let msg = match msg {
+            Some(msg) => unwrap_helper::continue_default!(msg.ok()),
+            None => break
+        };
         let data = match msg {
             tokio_tungstenite::tungstenite::Message::Binary(data) => data,
             _ => continue
@@ -124,9 +138,15 @@ async fn accept_connection(
         let socket_data_serialized = socket_data.to_string();
 
         router.route(&event, socket_data).await;
-        router.server.set_last_time_alive(get_u64_time_millis()).await;
+        let time_millis = get_u64_time_millis();
+        router.server.set_last_time_alive(time_millis).await;
+        if time_millis >= presence_refreshed_at + SERVER_PRESENCE_REFRESH_MS {
+            PlayerPresence::refresh_server(&router.server.api_state, &server_id).await;
+            presence_refreshed_at = time_millis;
+        };
         info!("[{}:{}] {}", server_id, event, socket_data_serialized);
     }
+    socket_session.api_state.server_outbox.unregister(&server_id, &connection_id).await;
     info!("WebSocket connection closed from server {}", socket_session.server_id.clone());
     let _ = router.server.stream.close(Some(CloseFrame { code: CloseCode::Normal, reason: std::borrow::Cow::Borrowed("Connection closed")  })).await;
 
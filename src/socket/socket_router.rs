@@ -8,7 +8,7 @@ use uuid::Uuid;
 
 use crate::{socket::r#match::match_phase_listener::MatchPhaseListener, util::{r#macro::unwrap_helper, time::get_u64_time_millis}, database::models::{r#match::{MatchState, FirstBlood}, player::Player, participant::{Participant, SimpleParticipant}, death::Death}};
 
-use super::{server::{server_context::{ServerContext}, server_events::MatchLoadData}, event_type::EventType, r#match::match_events::{MatchStartData, MatchEndData}, participant::{participant_stat_listener::ParticipantStatListener, participant_party_listener::ParticipantPartyListener}, player::{player_listener::PlayerListener, player_stat_listener::PlayerStatListener, player_events::{PlayerDeathData, PlayerChatData, KillstreakData, PartyJoinData, PartyLeaveData}, player_gamemode_stat_listener::PlayerGamemodeStatListener, player_xp_listener::PlayerXPListener, player_record_listener::PlayerRecordListener}, map::map_record_listener::MapRecordListener, leaderboard::leaderboard_listener::LeaderboardListener, objective::objective_events::{DestroyableDamageData, DestroyableDestroyData, CoreLeakData, ControlPointCaptureData, FlagDropData, FlagEventData, WoolDropData, WoolEventData}};
+use super::{server::{server_context::{ServerContext}, server_events::MatchLoadData}, event_type::EventType, r#match::match_events::{MatchStartData, MatchEndData}, participant::{participant_stat_listener::ParticipantStatListener, participant_party_listener::ParticipantPartyListener}, player::{player_listener::PlayerListener, player_stat_listener::PlayerStatListener, player_events::{PlayerDeathData, PlayerChatData, KillstreakData, PartyJoinData, PartyLeaveData}, player_gamemode_stat_listener::PlayerGamemodeStatListener, player_xp_listener::PlayerXPListener, player_record_listener::PlayerRecordListener, player_rating_listener::PlayerRatingListener}, map::{map_record_listener::MapRecordListener, map_stats_listener::MapStatsListener, map_record_history_listener::MapRecordHistoryListener}, leaderboard::leaderboard_listener::LeaderboardListener, objective::objective_events::{DestroyableDamageData, DestroyableDestroyData, CoreLeakData, ControlPointCaptureData, FlagDropData, FlagEventData, WoolDropData, WoolEventData}};
 
 pub struct SocketRouter {
     pub server: ServerContext,
@@ -45,6 +45,7 @@ impl SocketRouter {
                 Box::new(PlayerGamemodeStatListener {}),
                 Box::new(PlayerXPListener {}),
                 Box::new(PlayerRecordListener {}),
+                Box::new(PlayerRatingListener {}),
             ]
         }
     }
@@ -116,6 +117,8 @@ impl SocketRouter {
             Err(socket_error) => return Err(socket_error)
         };
 
+        data.rating_changes = PlayerRatingListener::compute_rating_changes(&self.server.api_state, &current_match, &data).await;
+
         // swap to avoid partial move
         let participants = current_match.participants;
         current_match.participants = HashMap::new();
@@ -140,6 +143,9 @@ impl SocketRouter {
             profiles.push(player);
         }
 
+        LeaderboardListener::on_match_end_batch(&self.server, &current_match, &data).await;
+        MapStatsListener::on_match_end(&self.server, &current_match, &data).await;
+
         if profiles.len() > 0 {
             let mut tasks : Vec<_> = Vec::new();
             for profile in profiles.iter() {
@@ -149,6 +155,7 @@ impl SocketRouter {
         };
 
         {
+            MapRecordHistoryListener::on_match_end(&self.server, &current_match).await;
             self.server.api_state.database.save(&current_match.level).await;
             self.server.api_state.match_cache.set_with_expiry(&self.server.api_state.database, &current_match.id, &current_match, true, Some(3_600_000)).await;
         };
@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use strum::IntoEnumIterator;
+
+use crate::{MarsAPIState, socket::leaderboard::ScoreType};
+
+const ARCHIVE_INTERVAL_SECONDS: u64 = 600;
+
+pub async fn run_leaderboard_archiver(state: MarsAPIState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(ARCHIVE_INTERVAL_SECONDS));
+    loop {
+        interval.tick().await;
+        for score_type in ScoreType::iter() {
+            score_type.to_leaderboard(&state.leaderboards).archive_finished_periods().await;
+        };
+    }
+}
@@ -0,0 +1,163 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures::StreamExt;
+use mongodb::bson::doc;
+use strum::IntoEnumIterator;
+
+use crate::{MarsAPIState, database::models::{r#match::Match, participant::Participant}, socket::{leaderboard::{LeaderboardPeriod, LeaderboardScope, ScoreType, MarsLeaderboards, snapshot_boards_for_rebuild, clear_rebuild_snapshots}, participant::participant_context::PlayerMatchResult, r#match::match_events::MatchEndData}, util::{r#macro::unwrap_helper, time::get_u64_time_millis}};
+
+const PROGRESS_INTERVAL_MATCHES: u64 = 500;
+
+// None when the score can't be derived from a stored match (xp and server playtime aren't match-bound)
+fn get_participant_score(score_type: &ScoreType, stored_match: &Match, participant: &Participant) -> Option<u32> {
+    let stats = &participant.stats;
+    let result = stored_match.winning_parties.as_ref().map(|winning_parties| {
+        participant.get_match_result(stored_match, &MatchEndData { winning_parties: winning_parties.clone(), big_stats: HashMap::new(), rating_changes: HashMap::new() })
+    });
+    match score_type {
+        ScoreType::Kills => Some(stats.kills),
+        ScoreType::Deaths => Some(stats.deaths),
+        ScoreType::FirstBloods => Some(stored_match.first_blood.as_ref().map_or(0, |first_blood| (first_blood.attacker.id == participant.id) as u32)),
+        ScoreType::Wins => result.map(|result| matches!(result, PlayerMatchResult::Win) as u32),
+        ScoreType::Losses => result.map(|result| matches!(result, PlayerMatchResult::Lose) as u32),
+        ScoreType::Ties => result.map(|result| matches!(result, PlayerMatchResult::Tie) as u32),
+        ScoreType::Xp | ScoreType::ServerPlaytime => None,
+        ScoreType::MessagesSent => Some(stats.messages.total()),
+        ScoreType::MatchesPlayed => Some(1),
+        ScoreType::GamePlaytime => Some(u32::try_from(stats.game_playtime).unwrap_or(0)),
+        ScoreType::CoreLeaks => Some(stats.objectives.core_leaks),
+        ScoreType::CoreBlockDestroys => Some(stats.objectives.core_block_destroys),
+        ScoreType::DestroyableDestroys => Some(stats.objectives.destroyable_destroys),
+        ScoreType::DestroyableBlockDestroys => Some(stats.objectives.destroyable_block_destroys),
+        ScoreType::FlagCaptures => Some(stats.objectives.flag_captures),
+        ScoreType::FlagDrops => Some(stats.objectives.flag_drops),
+        ScoreType::FlagPickups => Some(stats.objectives.flag_pickups),
+        ScoreType::FlagDefends => Some(stats.objectives.flag_defends),
+        ScoreType::FlagHoldTime => Some(u32::try_from(stats.objectives.total_flag_hold_time).unwrap_or(0)),
+        ScoreType::WoolCaptures => Some(stats.objectives.wool_captures),
+        ScoreType::WoolDrops => Some(stats.objectives.wool_drops),
+        ScoreType::WoolPickups => Some(stats.objectives.wool_pickups),
+        ScoreType::WoolDefends => Some(stats.objectives.wool_defends),
+        ScoreType::ControlPointCaptures => Some(stats.objectives.control_point_captures),
+        ScoreType::HighestKillstreak => Some(stats.killstreaks.keys().max().cloned().unwrap_or(0))
+    }
+}
+
+// resets the status even when the rebuild panics or bails out early
+struct RebuildRunningGuard {
+    leaderboards: Arc<MarsLeaderboards>
+}
+
+impl Drop for RebuildRunningGuard {
+    fn drop(&mut self) {
+        let leaderboards = Arc::clone(&self.leaderboards);
+        tokio::spawn(async move {
+            leaderboards.rebuild_status.lock().await.running = false;
+        });
+    }
+}
+
+// recomputes every period of every board from matches that ended between `since` and the start of the rebuild.
+// global all-time boards come from player stats. scoped boards aren't archived so only their current periods are
+// rebuilt, and boards of periods that began before `since` are left as they are since they'd only be partially covered.
+// matches stored before winning parties were recorded are counted in matches_without_results and add no wins, losses or ties
+pub async fn run_leaderboard_rebuild(state: MarsAPIState) {
+    let _running_guard = RebuildRunningGuard { leaderboards: Arc::clone(&state.leaderboards) };
+    let leaderboards = &state.leaderboards;
+    let periods = leaderboards.periods;
+    let (started_at, since) = {
+        let status = leaderboards.rebuild_status.lock().await;
+        (status.started_at.unwrap_or(get_u64_time_millis()), status.since)
+    };
+    snapshot_boards_for_rebuild(&state.redis).await;
+
+    let filter = doc! { "endedAt": { "$gte": since as i64, "$lt": started_at as i64 } };
+    let matches_total = state.database.matches.count_documents(filter.clone(), None).await.unwrap_or(0);
+    leaderboards.rebuild_status.lock().await.matches_total = matches_total;
+    info!("Rebuilding leaderboards from {} matches", matches_total);
+
+    let current_period_ids : Vec<String> = LeaderboardPeriod::iter().map(|period| period.get_today_id(&periods)).collect();
+    // ids still being written to on the day before `since`
+    let uncovered_period_ids : Vec<String> = match periods.date_of(since).pred_opt() {
+        Some(day_before) if since > 0 => LeaderboardPeriod::iter().map(|period| period.get_id_for_date(&day_before, &periods)).collect(),
+        _ => Vec::new()
+    };
+    let mut boards : HashMap<(ScoreType, LeaderboardScope, String), HashMap<String, u64>> = HashMap::new();
+    let mut matches_processed : u64 = 0;
+    let mut matches_without_results : u64 = 0;
+    if let Ok(mut cursor) = state.database.matches.find(filter, None).await {
+        while let Some(stored_match) = cursor.next().await {
+            matches_processed += 1;
+            if matches_processed % PROGRESS_INTERVAL_MATCHES == 0 {
+                let mut status = leaderboards.rebuild_status.lock().await;
+                status.matches_processed = matches_processed;
+                status.matches_without_results = matches_without_results;
+            };
+            let stored_match = unwrap_helper::continue_default!(stored_match.ok());
+            if !stored_match.is_tracking_stats() {
+                continue;
+            };
+            if stored_match.winning_parties.is_none() {
+                matches_without_results += 1;
+            };
+            let date = periods.date_of(unwrap_helper::continue_default!(stored_match.ended_at));
+            let match_period_ids : Vec<(LeaderboardPeriod, String)> = LeaderboardPeriod::iter()
+                .map(|period| {
+                    let period_id = period.get_id_for_date(&date, &periods);
+                    (period, period_id)
+                })
+                .filter(|(_, period_id)| !uncovered_period_ids.contains(period_id))
+                .collect();
+            let mut scopes = LeaderboardScope::of_match(&stored_match);
+            scopes.push(LeaderboardScope::Global);
+
+            for participant in stored_match.participants.values() {
+                for score_type in ScoreType::iter() {
+                    let score = unwrap_helper::continue_default!(get_participant_score(&score_type, &stored_match, participant)) as u64;
+                    if score == 0 {
+                        continue;
+                    };
+                    for scope in scopes.iter() {
+                        for (period, period_id) in match_period_ids.iter() {
+                            if *scope == LeaderboardScope::Global && *period == LeaderboardPeriod::AllTime {
+                                continue;
+                            };
+                            if *scope != LeaderboardScope::Global && !current_period_ids.contains(period_id) {
+                                continue;
+                            };
+                            let members = boards.entry((score_type.clone(), scope.clone(), period_id.clone())).or_insert(HashMap::new());
+                            let member_score = members.entry(participant.get_id_name()).or_insert(0);
+                            if score_type == ScoreType::HighestKillstreak {
+                                *member_score = (*member_score).max(score);
+                            } else {
+                                *member_score += score;
+                            };
+                        };
+                    };
+                };
+            };
+        };
+    };
+    {
+        let mut status = leaderboards.rebuild_status.lock().await;
+        status.matches_processed = matches_processed;
+        status.matches_without_results = matches_without_results;
+    };
+    if matches_without_results > 0 {
+        warn!("{} matches have no stored results, their wins, losses and ties were not rebuilt", matches_without_results);
+    };
+
+    for score_type in ScoreType::iter() {
+        score_type.to_leaderboard(leaderboards).populate_all_time().await;
+    };
+    for ((score_type, scope, period_id), members) in boards.into_iter() {
+        let members : Vec<(u64, String)> = members.into_iter().map(|(member, score)| (score, member)).collect();
+        score_type.to_leaderboard(leaderboards).scoped(scope).replace_period(&period_id, &members).await;
+        leaderboards.rebuild_status.lock().await.boards_written += 1;
+    };
+    clear_rebuild_snapshots(&state.redis).await;
+
+    let mut status = leaderboards.rebuild_status.lock().await;
+    status.finished_at = Some(get_u64_time_millis());
+    info!("Rebuilt {} leaderboards from {} matches", status.boards_written, matches_processed);
+}
@@ -0,0 +1,5 @@
+pub mod leaderboard_archive;
+pub mod leaderboard_rebuild;
+pub mod rank_expiry;
+pub mod ratio_leaderboards;
+pub mod xp_multiplier_expiry;
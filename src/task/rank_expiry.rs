@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use mongodb::bson::doc;
+
+use crate::{MarsAPIState, database::{Database, models::player::Player}, util::time::get_u64_time_millis};
+
+const SWEEP_INTERVAL_SECONDS: u64 = 60;
+
+pub async fn run_rank_expiry_sweeper(state: MarsAPIState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECONDS));
+    loop {
+        interval.tick().await;
+        sweep_expired_rank_grants(&state).await;
+    }
+}
+
+async fn sweep_expired_rank_grants(state: &MarsAPIState) {
+    let expired_players : Vec<Player> = Database::consume_cursor_into_owning_vec_option(state.database.players.find(doc! {
+        "rankGrants.expiresAt": { "$lte": get_u64_time_millis() as i64 }
+    }, None).await.ok()).await;
+
+    for stored_player in expired_players {
+        // the cached copy may be ahead of the database while the player is online
+        let mut player = state.player_cache.get(&state.database, &stored_player.name).await.unwrap_or(stored_player);
+        let expired_grants = player.remove_expired_rank_grants();
+        if expired_grants.is_empty() {
+            continue;
+        };
+        state.player_cache.set(&state.database, &player.name, &player, true).await;
+        info!(
+            "Removed expired ranks from {}: {}", 
+            player.id_name(), 
+            expired_grants.iter().map(|grant| grant.rank_id.clone()).collect::<Vec<String>>().join(", ")
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use std::time::Duration;
+
+use crate::MarsAPIState;
+
+const RECOMPUTE_INTERVAL_SECONDS: u64 = 900;
+
+pub async fn run_ratio_leaderboard_recomputer(state: MarsAPIState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(RECOMPUTE_INTERVAL_SECONDS));
+    loop {
+        interval.tick().await;
+        state.leaderboards.ratios.recompute(&state.config.options).await;
+    }
+}
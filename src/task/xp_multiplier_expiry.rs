@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use mongodb::bson::doc;
+
+use crate::{MarsAPIState, util::time::get_u64_time_millis};
+
+const SWEEP_INTERVAL_SECONDS: u64 = 300;
+
+pub async fn run_xp_multiplier_expiry_sweeper(state: MarsAPIState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECONDS));
+    loop {
+        interval.tick().await;
+        if let Ok(delete_result) = state.database.xp_multiplier_windows.delete_many(doc! {
+            "endsAt": {"$lte": get_u64_time_millis() as i64}
+        }, None).await {
+            if delete_result.deleted_count > 0 {
+                info!("Removed {} expired XP multipliers", delete_result.deleted_count);
+            };
+        };
+    }
+}
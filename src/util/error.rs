@@ -51,6 +51,14 @@ impl ApiErrorResponder {
         )
     }
 
+    pub fn session_already_active() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict, 
+            &ApiExceptionType::SessionAlreadyActive, 
+            "The player already has an active session"
+        )
+    }
+
     pub fn rank_confict() -> Self {
         ApiErrorResponder::create_api_error_responder(
             Status::Conflict, 
@@ -67,6 +75,14 @@ impl ApiErrorResponder {
         )
     }
 
+    pub fn rank_inheritance_cycle() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::BadRequest, 
+            &ApiExceptionType::RankInheritanceCycle, 
+            "The rank would inherit from itself"
+        )
+    }
+
     pub fn missing_map() -> Self {
         ApiErrorResponder::create_api_error_responder(
             Status::NotFound, 
@@ -75,6 +91,78 @@ impl ApiErrorResponder {
         )
     }
 
+    pub fn missing_match() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::MatchMissing, 
+            "The match does not exist"
+        )
+    }
+
+    pub fn missing_rotation() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::RotationMissing, 
+            "The rotation does not exist"
+        )
+    }
+
+    pub fn rotation_conflict() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict, 
+            &ApiExceptionType::RotationConflict, 
+            "A rotation already exists with that name"
+        )
+    }
+
+    pub fn missing_vote_session() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::VoteSessionMissing, 
+            "There is no map vote running on the server"
+        )
+    }
+
+    pub fn vote_session_running() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict, 
+            &ApiExceptionType::VoteSessionRunning, 
+            "A map vote is already running on the server"
+        )
+    }
+
+    pub fn missing_xp_multiplier() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::XpMultiplierMissing, 
+            "The XP multiplier does not exist"
+        )
+    }
+
+    pub fn missing_leaderboard_snapshot() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::LeaderboardSnapshotMissing, 
+            "The leaderboard snapshot does not exist"
+        )
+    }
+
+    pub fn missing_leaderboard_entry() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::LeaderboardEntryMissing, 
+            "The player is not on this leaderboard"
+        )
+    }
+
+    pub fn leaderboard_rebuild_running() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Conflict, 
+            &ApiExceptionType::LeaderboardRebuildRunning, 
+            "A leaderboard rebuild is already running"
+        )
+    }
+
     pub fn missing_player() -> Self {
         ApiErrorResponder::create_api_error_responder(
             Status::NotFound, 
@@ -150,6 +238,22 @@ impl ApiErrorResponder {
             "A tag already exists with that name"
         )
     }
+
+    pub fn join_sound_missing() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::NotFound, 
+            &ApiExceptionType::JoinSoundMissing, 
+            "The join sound does not exist"
+        )
+    }
+
+    pub fn join_sound_locked() -> Self {
+        ApiErrorResponder::create_api_error_responder(
+            Status::Forbidden, 
+            &ApiExceptionType::JoinSoundLocked, 
+            "The player has not unlocked the join sound"
+        )
+    }
 }
 
 impl<'r> Responder<'r, 'static> for ApiErrorResponder {
@@ -205,16 +309,29 @@ enum ApiExceptionType {
     ValidationError,
     SessionMissing,
     SessionInactive,
+    SessionAlreadyActive,
     PlayerMissing,
     RankConflict,
     RankMissing,
     RankAlreadyPresent,
     RankNotPresent,
+    RankInheritanceCycle,
     TagConflict,
     TagMissing,
     TagAlreadyPresent,
     TagNotPresent,
+    JoinSoundMissing,
+    JoinSoundLocked,
     MapMissing,
+    MatchMissing,
+    RotationMissing,
+    RotationConflict,
+    VoteSessionMissing,
+    VoteSessionRunning,
+    XpMultiplierMissing,
+    LeaderboardSnapshotMissing,
+    LeaderboardEntryMissing,
+    LeaderboardRebuildRunning,
     PunishmentMissing,
     NoteMissing,
     Anonymous